@@ -0,0 +1,219 @@
+use crate::error::{Cause, MeowError, MeowResult, Stage};
+use std::collections::{BTreeMap, HashSet};
+
+fn aliaserror(message: String) -> MeowError {
+    MeowError::pipeline(Stage::Setup, Cause::InvalidConfig, message)
+}
+
+/// splits an alias into arguments the way a shell would: whitespace separates
+/// them, quotes group them and a backslash escapes the next character
+pub fn splitargs(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    // whether `current` holds an argument, even an empty quoted one
+    let mut started = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                current.push(chars.next()?);
+                started = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started {
+                    args.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return None;
+    }
+    if started {
+        args.push(current);
+    }
+    Some(args)
+}
+
+/// substitutes `$1`..`$9` and `$@` in `words` with `args`. args no placeholder
+/// refers to are appended, as git does for its aliases
+pub fn fillplaceholders(name: &str, words: &[String], args: &[String]) -> MeowResult<Vec<String>> {
+    let mut used = vec![false; args.len()];
+    let mut usedall = false;
+    let mut filled = Vec::new();
+
+    for word in words {
+        // a lone $@ becomes one argument per arg rather than one joined argument
+        if word == "$@" {
+            filled.extend(args.iter().cloned());
+            usedall = true;
+            continue;
+        }
+
+        let mut out = String::new();
+        let mut chars = word.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek().copied()) {
+                ('$', Some('@')) => {
+                    chars.next();
+                    out.push_str(&args.join(" "));
+                    usedall = true;
+                }
+                ('$', Some(digit @ '1'..='9')) => {
+                    chars.next();
+                    let index = digit as usize - '1' as usize;
+                    let arg = args.get(index).ok_or_else(|| {
+                        aliaserror(format!(
+                            "alias `{}` uses ${} but was given {} argument(s)",
+                            name,
+                            digit,
+                            args.len()
+                        ))
+                    })?;
+                    out.push_str(arg);
+                    used[index] = true;
+                }
+                (c, _) => out.push(c),
+            }
+        }
+        filled.push(out);
+    }
+
+    if !usedall {
+        filled.extend(
+            args.iter()
+                .zip(used)
+                .filter(|(_, used)| !used)
+                .map(|(arg, _)| arg.clone()),
+        );
+    }
+    Ok(filled)
+}
+
+/// expands the alias named by the first argument (after the program name),
+/// following aliases of aliases. built-in commands can't be shadowed
+pub fn expandaliases(
+    mut argv: Vec<String>,
+    aliases: &BTreeMap<String, String>,
+    builtins: &[&str],
+) -> MeowResult<Vec<String>> {
+    let mut seen = HashSet::new();
+
+    while let Some(name) = argv.get(1).cloned()
+        && !builtins.contains(&name.as_str())
+        && let Some(expansion) = aliases.get(&name)
+    {
+        if !seen.insert(name.clone()) {
+            return Err(aliaserror(format!("alias `{}` expands to itself", name)));
+        }
+
+        let words = splitargs(expansion)
+            .ok_or_else(|| aliaserror(format!("alias `{}` has an unclosed quote", name)))?;
+        let rest = argv.split_off(2);
+        argv.truncate(1);
+        argv.extend(fillplaceholders(&name, &words, &rest)?);
+    }
+    Ok(argv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn splitwhitespace() {
+        assert_eq!(
+            splitargs("  -a .   \"wip\" "),
+            Some(strings(&["-a", ".", "wip"]))
+        );
+        assert_eq!(splitargs(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn splitquotes() {
+        assert_eq!(
+            splitargs(r#"commit "fix: a b" 'it''s'"#),
+            Some(strings(&["commit", "fix: a b", "its"]))
+        );
+        assert_eq!(splitargs(r#"-m "" x"#), Some(strings(&["-m", "", "x"])));
+        assert_eq!(splitargs(r#"'a "b" c'"#), Some(strings(&[r#"a "b" c"#])));
+    }
+
+    #[test]
+    fn splitescapes() {
+        assert_eq!(splitargs(r"a\ b c"), Some(strings(&["a b", "c"])));
+        assert_eq!(
+            splitargs(r#""say \"hi\"""#),
+            Some(strings(&[r#"say "hi""#]))
+        );
+        // single quotes keep backslashes, as in a shell
+        assert_eq!(splitargs(r"'a\b'"), Some(strings(&[r"a\b"])));
+    }
+
+    #[test]
+    fn splitunclosed() {
+        assert_eq!(splitargs("commit \"wip"), None);
+        assert_eq!(splitargs("a 'b"), None);
+        assert_eq!(splitargs(r"trailing\"), None);
+    }
+
+    #[test]
+    fn fillnumbered() {
+        let words = strings(&["-a", "$1", "wip: $2"]);
+        assert_eq!(
+            fillplaceholders("wip", &words, &strings(&["src/", "login"])).unwrap(),
+            strings(&["-a", "src/", "wip: login"])
+        );
+    }
+
+    #[test]
+    fn fillappendsunused() {
+        let words = strings(&["commit", "$2"]);
+        assert_eq!(
+            fillplaceholders("c", &words, &strings(&["one", "two", "three"])).unwrap(),
+            strings(&["commit", "two", "one", "three"])
+        );
+        assert_eq!(
+            fillplaceholders("p", &strings(&["push"]), &strings(&["-f"])).unwrap(),
+            strings(&["push", "-f"])
+        );
+    }
+
+    #[test]
+    fn fillall() {
+        let args = strings(&["a b", "c"]);
+        // a lone $@ keeps the arguments apart, inside a word they're joined
+        assert_eq!(
+            fillplaceholders("s", &strings(&["stage", "$@"]), &args).unwrap(),
+            strings(&["stage", "a b", "c"])
+        );
+        assert_eq!(
+            fillplaceholders("m", &strings(&["wip: $@"]), &args).unwrap(),
+            strings(&["wip: a b c"])
+        );
+    }
+
+    #[test]
+    fn fillmissingargument() {
+        let error = fillplaceholders("wip", &strings(&["$2"]), &strings(&["one"])).unwrap_err();
+        assert_eq!(*error.cause(), Cause::InvalidConfig);
+    }
+}
@@ -1,4 +1,10 @@
-use clap::Parser;
+use crate::completions::Shell;
+use clap::{
+    ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, error::ErrorKind,
+    parser::ValueSource,
+};
+use meow::{release::Bump, theme::ColorChoice};
+use std::{ffi::OsString, path::PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -7,94 +13,725 @@ use clap::Parser;
     about,
     long_about = None,
     disable_version_flag = true,
-    disable_help_flag = true
+    disable_help_flag = true,
+    disable_help_subcommand = true,
+    override_usage = "meow [options] <message>\n       meow <command> [options]",
+    subcommand_negates_reqs = true
 )]
-
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub stage: StageArgs,
+
     #[arg(
-        short = 'a',
-        long = "add",
-        name = "files",
-        help = "specify files to stage"
+        name = "message",
+        help = "commit message",
+        long_help = "commit message. meow stages everything (or the -a files), commits with \
+                     this message and pushes",
+        required_unless_present_any = &["run", "meow", "version"]
     )]
-    pub add: Option<Vec<String>>,
+    pub commitmessage: Option<String>,
+
+    #[command(flatten)]
+    pub commit: CommitArgs,
+
+    #[command(flatten)]
+    pub tag: TagArgs,
+
+    #[command(flatten)]
+    pub push: PushArgs,
+
+    #[command(flatten)]
+    pub repos: RepoArgs,
 
     #[arg(
         short = 'd',
         long = "dry-run",
+        global = true,
+        help_heading = "pipeline",
         help = "runs meow without running commands"
     )]
     pub dryrun: bool,
 
     #[arg(
-        name = "message",
-        help = "commit message",
-        required_unless_present_any = &[
-            "run",
-            "meow",
-            "help",
-            "pushonly",
-            "version",
-            "addremote",
-            "removeremote"
-        ]
+        long = "verbose",
+        short = 'v',
+        global = true,
+        help_heading = "output",
+        help = "debug output, -vv also traces every git command",
+        long_help = "prints debug output to stderr. -vv also traces every git command meow \
+                     runs: its working directory, GIT_* environment, exit code, timing and \
+                     full stdout and stderr",
+        action = clap::ArgAction::Count
     )]
-    pub commitmessage: Option<String>,
+    pub verbose: u8,
+
+    #[arg(
+        long = "quiet",
+        short = 'q',
+        global = true,
+        conflicts_with = "verbose",
+        help_heading = "output",
+        help = "only prints errors (and what a prompt is asking about)"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long = "log",
+        global = true,
+        help_heading = "output",
+        help = "writes everything, debug output included, to the log file",
+        long_help = "writes everything, including debug and trace output, to meow.log in \
+                     $XDG_STATE_HOME/meow (or ~/.local/state/meow), whatever -v or -q say. \
+                     set enabled under [log] in the config to always log"
+    )]
+    pub log: bool,
+
+    #[arg(
+        long = "exit",
+        short = 'E',
+        global = true,
+        help_heading = "pipeline",
+        help = "exits meow on error"
+    )]
+    pub exitonerror: bool,
+
+    #[arg(
+        long = "color",
+        value_name = "when",
+        global = true,
+        help_heading = "output",
+        help = "when to use color (overrides NO_COLOR and CLICOLOR_FORCE)"
+    )]
+    pub color: Option<ColorChoice>,
+
+    #[arg(
+        long = "ascii",
+        global = true,
+        help_heading = "output",
+        help = "plain ascii output, without emoji or arrows"
+    )]
+    pub ascii: bool,
 
-    #[arg(long = "version", short = 'V', help = "print version")]
+    #[arg(
+        long = "version",
+        short = 'V',
+        help_heading = "output",
+        help = "print version"
+    )]
     pub version: bool,
 
-    #[arg(short = 'h', long = "help", help = "prints help")]
+    #[arg(
+        short = 'h',
+        global = true,
+        help_heading = "output",
+        help = "prints help"
+    )]
     pub help: bool,
 
+    #[arg(
+        long = "help",
+        global = true,
+        help_heading = "output",
+        help = "prints help with details and examples"
+    )]
+    pub longhelp: bool,
+
     #[arg(long = "meow", hide = true)]
     pub meow: bool,
 
     #[arg(long = "run", short = 'r', help = "run git commands", hide = true)]
     pub run: bool,
+}
+
+impl Args {
+    /// names of the built-in commands, which aliases can't shadow
+    pub fn commandnames() -> Vec<String> {
+        Args::command()
+            .get_subcommands()
+            .map(|c| c.get_name().to_string())
+            .collect()
+    }
+
+    /// like `try_parse_from`, but answers -h/--help before checking required
+    /// arguments, and rejects pipeline flags given before a subcommand, which
+    /// would otherwise be ignored
+    pub fn parsecommandline(argv: Vec<OsString>) -> Result<Invocation, clap::Error> {
+        let mut cmd = Args::command();
+
+        // a help request shouldn't fail for want of the command's arguments
+        if let Ok(probe) = cmd
+            .clone()
+            .ignore_errors(true)
+            .try_get_matches_from(argv.clone())
+        {
+            let (path, leaf) = subcommandpath(&probe);
+            let flag = |id: &str| leaf.try_get_one::<bool>(id).ok().flatten() == Some(&true);
+            if flag("longhelp") || flag("help") {
+                return Ok(Invocation::Help {
+                    path,
+                    long: flag("longhelp"),
+                });
+            }
+        }
+
+        let matches = cmd.try_get_matches_from_mut(argv)?;
+
+        if let Some((name, _)) = matches.subcommand() {
+            let stray = cmd.get_arguments().find(|arg| {
+                !arg.is_global_set()
+                    && matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+            if let Some(arg) = stray {
+                let flag = match (arg.get_long(), arg.get_short()) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some(short)) => format!("-{}", short),
+                    (None, None) => arg.get_id().to_string(),
+                };
+                return Err(cmd.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument '{}' cannot be used with '{}'", flag, name),
+                ));
+            }
+        }
+
+        Args::from_arg_matches(&matches).map(|args| Invocation::Run(Box::new(args)))
+    }
+}
+
+/// flags from before the subcommands: the flag, the arguments that replace
+/// it, and the command to suggest
+const LEGACYFLAGS: &[(&str, &[&str], &str)] = &[
+    ("-p", &["push"], "meow push"),
+    ("--push", &["push"], "meow push"),
+    ("-c", &["commit"], "meow commit <message>"),
+    ("--commit", &["commit"], "meow commit <message>"),
+    ("-s", &["stage"], "meow stage [files]"),
+    ("--stage", &["stage"], "meow stage [files]"),
+    (
+        "--add-remote",
+        &["remote", "add", "origin"],
+        "meow remote add <name> <url>",
+    ),
+    (
+        "--radd",
+        &["remote", "add", "origin"],
+        "meow remote add <name> <url>",
+    ),
+    (
+        "--remove-remote",
+        &["remote", "remove"],
+        "meow remote remove <name>",
+    ),
+    ("--rrm", &["remote", "remove"], "meow remote remove <name>"),
+];
+
+type LegacyFlag = (&'static str, &'static [&'static str], &'static str);
+
+fn legacyflag(flag: &str) -> Option<&'static LegacyFlag> {
+    LEGACYFLAGS.iter().find(|(old, _, _)| *old == flag)
+}
+
+// finds a legacy flag in `-pf`-style bundled short flags, returning it and
+// what's left of the bundle. letters after one that takes a value are that
+// value, so `-uprod` is left alone
+fn splitbundle(arg: &str) -> Option<(&'static LegacyFlag, String)> {
+    let letters = arg.strip_prefix('-').filter(|l| !l.starts_with('-'))?;
+    let cmd = Args::command();
+    for (idx, letter) in letters.char_indices() {
+        if let Some(legacy) = legacyflag(&format!("-{}", letter)) {
+            let rest = format!("-{}{}", &letters[..idx], &letters[idx + 1..]);
+            return Some((legacy, rest));
+        }
+        let known = cmd
+            .get_arguments()
+            .find(|a| a.get_short() == Some(letter))?;
+        if known.get_action().takes_values() {
+            return None;
+        }
+    }
+    None
+}
+
+/// rewrites a flag that has since become a subcommand, e.g. `meow -p -f` or
+/// `meow -pf` into `meow push -f`, so existing scripts keep working. returns
+/// the flag that was replaced and the command to use instead, for a
+/// deprecation hint
+pub fn replacelegacyflags(argv: Vec<OsString>) -> (Vec<OsString>, Option<(String, &'static str)>) {
+    let commands = Args::commandnames();
+    let found = argv
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(idx, arg)| (idx, arg.to_str().unwrap_or_default()))
+        .take_while(|(_, arg)| *arg != "--" && !commands.iter().any(|c| c == arg))
+        .find_map(|(idx, arg)| match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                legacyflag(flag).map(|legacy| (idx, legacy, Some(OsString::from(value))))
+            }
+            _ if arg.starts_with("--") => legacyflag(arg).map(|legacy| (idx, legacy, None)),
+            // what's left of a bundle like `-pf` stays where the bundle was
+            _ => splitbundle(arg)
+                .map(|(legacy, rest)| (idx, legacy, (rest != "-").then(|| OsString::from(rest)))),
+        });
+    let Some((flagidx, &(old, new, suggestion), mut value)) = found else {
+        return (argv, None);
+    };
+
+    // --remove-remote on its own meant origin
+    let hasvalue = value.is_some()
+        || argv
+            .get(flagidx + 1)
+            .and_then(|arg| arg.to_str())
+            .is_some_and(|arg| !arg.starts_with('-'));
+    let mut rewritten: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    rewritten.extend(new.iter().map(OsString::from));
+    if new == ["remote", "remove"] && !hasvalue {
+        rewritten.push(OsString::from("origin"));
+    }
+    // `meow stage` takes its files directly, where -s took them from -a
+    for (idx, arg) in argv.into_iter().enumerate().skip(1) {
+        let arg = if idx == flagidx {
+            match value.take() {
+                Some(value) => value,
+                None => continue,
+            }
+        } else {
+            arg
+        };
+        if !(new == ["stage"] && (arg == "-a" || arg == "--add")) {
+            rewritten.push(arg);
+        }
+    }
+
+    (rewritten, Some((old.to_string(), suggestion)))
+}
+
+pub enum Invocation {
+    Run(Box<Args>),
+    /// help for the command named by `path`, e.g. ["remote", "add"]
+    Help {
+        path: Vec<String>,
+        long: bool,
+    },
+}
+
+// the names of the subcommands that were matched, and the innermost matches
+fn subcommandpath(matches: &ArgMatches) -> (Vec<String>, &ArgMatches) {
+    let mut path = Vec::new();
+    let mut leaf = matches;
+    while let Some((name, sub)) = leaf.subcommand() {
+        path.push(name.to_string());
+        leaf = sub;
+    }
+    (path, leaf)
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// stages changes without committing
+    Stage {
+        #[arg(name = "files", help = "files to stage (defaults to everything)")]
+        files: Vec<String>,
+
+        #[arg(
+            short = 'i',
+            long = "interactive",
+            conflicts_with_all = ["repos", "allrepos"],
+            help_heading = "staging",
+            help = "choose which hunks to stage",
+            long_help = "walks through each unstaged hunk and asks whether to stage, skip, \
+                         split or edit it"
+        )]
+        interactive: bool,
+
+        #[command(flatten)]
+        repos: RepoArgs,
+    },
+
+    /// commits what's staged (staging -a files first) without pushing
+    Commit {
+        #[command(flatten)]
+        stage: StageArgs,
+
+        #[arg(name = "message", help = "commit message")]
+        message: String,
+
+        #[command(flatten)]
+        commit: CommitArgs,
+
+        #[command(flatten)]
+        tag: TagArgs,
 
-    #[arg(long = "set-upstream", short = 'u', help = "sets upstream")]
+        #[command(flatten)]
+        repos: RepoArgs,
+    },
+
+    /// pushes without staging or committing
+    Push {
+        #[command(flatten)]
+        push: PushArgs,
+
+        #[command(flatten)]
+        repos: RepoArgs,
+    },
+
+    /// prints branch and sync status
+    Status {
+        #[command(flatten)]
+        repos: RepoArgs,
+    },
+
+    /// unstages files, or everything
+    Unstage {
+        #[arg(name = "files", help = "files to unstage (defaults to everything)")]
+        files: Vec<String>,
+    },
+
+    /// discards unstaged changes to files, or everything (asks first)
+    Restore {
+        #[arg(name = "files", help = "files to restore (defaults to everything)")]
+        files: Vec<String>,
+    },
+
+    /// adds or removes remotes
+    #[command(subcommand)]
+    Remote(RemoteCommand),
+
+    /// bumps the version from commits since the last tag, then commits, tags and pushes
+    Release {
+        #[arg(
+            name = "bump",
+            default_value = "auto",
+            help = "which part of the version to bump"
+        )]
+        bump: Bump,
+
+        #[arg(long = "changelog", help = "also updates CHANGELOG.md")]
+        changelog: bool,
+
+        #[arg(
+            long = "set-upstream",
+            short = 'u',
+            value_name = "branch",
+            help_heading = "pushing",
+            help = "sets upstream"
+        )]
+        upstream: Option<String>,
+    },
+
+    /// updates CHANGELOG.md from commit history
+    Changelog {
+        #[arg(
+            long = "from",
+            value_name = "ref",
+            help = "start ref (defaults to the last tag)"
+        )]
+        from: Option<String>,
+
+        #[arg(
+            long = "to",
+            value_name = "ref",
+            default_value = "HEAD",
+            help = "end ref"
+        )]
+        to: String,
+    },
+
+    /// prints a completion script for bash, zsh, fish or elvish
+    Completions {
+        #[arg(name = "shell")]
+        shell: Shell,
+    },
+
+    /// prints a man page, or writes one per command into a directory
+    Man {
+        #[arg(name = "dir", help = "writes meow.1 and meow-<command>.1 here")]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RemoteCommand {
+    /// same as git remote add
+    Add {
+        #[arg(name = "name", help = "remote name, e.g. origin")]
+        name: String,
+
+        #[arg(name = "url", help = "remote url")]
+        url: String,
+    },
+
+    /// same as git remote remove
+    Remove {
+        #[arg(name = "name", help = "remote name")]
+        name: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "staging")]
+pub struct StageArgs {
+    #[arg(
+        short = 'a',
+        long = "add",
+        name = "files",
+        help = "specify files to stage"
+    )]
+    pub add: Option<Vec<String>>,
+
+    #[arg(
+        short = 'i',
+        long = "interactive",
+        conflicts_with_all = ["repos", "allrepos"],
+        help = "choose which hunks to stage (from the files given to -a, or all)",
+        long_help = "walks through each unstaged hunk of the files given to -a (or of every \
+                     tracked file) and asks whether to stage, skip, split or edit it"
+    )]
+    pub interactive: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "pipeline")]
+pub struct CommitArgs {
+    #[arg(
+        long = "recurse-submodules",
+        help = "commits and pushes changed submodules without asking",
+        long_help = "commits and pushes submodules with changes before the superproject, \
+                     without asking first"
+    )]
+    pub recursesubmodules: bool,
+
+    #[arg(
+        long = "allow-secrets",
+        help = "commits even if the staged changes look like they contain secrets",
+        long_help = "commits even if the staged changes look like they contain secrets. \
+                     to silence a false positive for good, list it in .meow-allowlist instead"
+    )]
+    pub allowsecrets: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "pipeline")]
+pub struct TagArgs {
+    #[arg(
+        long = "tag",
+        short = 't',
+        value_name = "name",
+        conflicts_with_all = ["repos", "allrepos"],
+        help = "creates an annotated tag on the new commit and pushes it",
+        long_help = "creates an annotated tag on the new commit and pushes it along with the \
+                     branch. the tag message defaults to the commit message"
+    )]
+    pub tag: Option<String>,
+
+    #[arg(
+        long = "tag-message",
+        value_name = "message",
+        requires = "tag",
+        help = "tag message (defaults to the commit message)"
+    )]
+    pub tagmessage: Option<String>,
+
+    #[arg(long = "sign-tag", requires = "tag", help = "signs the tag with gpg")]
+    pub signtag: bool,
+
+    #[arg(
+        long = "force-tag",
+        requires = "tag",
+        help = "overwrites the tag if it already exists"
+    )]
+    pub forcetag: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "pushing")]
+pub struct PushArgs {
+    #[arg(
+        long = "set-upstream",
+        short = 'u',
+        value_name = "branch",
+        help = "sets upstream"
+    )]
     pub upstream: Option<String>,
 
     #[arg(
         long = "force",
         short = 'f',
-        help = "adds --force-with-lease",
+        help = "force pushes: -f with lease, -ff plain --force",
+        long_help = "force pushes. -f uses --force-with-lease and -ff uses plain --force. \
+                     meow lists the remote commits the push would discard and asks first",
         action = clap::ArgAction::Count
     )]
     pub force: u8,
 
     #[arg(
-        long = "verbose",
-        short = 'v',
-        help = "verbose output",
-        action = clap::ArgAction::Count
+        long = "if-includes",
+        requires = "force",
+        help = "adds --force-if-includes to -f (git 2.30+)"
     )]
-    pub verbose: u8,
+    pub ifincludes: bool,
 
-    #[arg(long = "exit", short = 'E', help = "exits meow on error")]
-    pub exitonerror: bool,
-
-    #[arg(long = "push", short = 'p', help = "pushes only")]
-    pub pushonly: bool,
-
-    #[arg(long = "commit", short = 'c', help = "commits only")]
-    pub commitonly: bool,
+    #[arg(
+        long = "force-protected",
+        help = "allows force pushing to protected branches"
+    )]
+    pub forceprotected: bool,
+}
 
-    #[arg(long = "stage", short = 's', help = "stages only")]
-    pub stageonly: bool,
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "repositories")]
+pub struct RepoArgs {
+    #[arg(
+        long = "repos",
+        short = 'R',
+        value_name = "glob",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with = "allrepos",
+        help = "runs meow in every repo matching the glob (or the repos listed in config)",
+        long_help = "runs meow in every repo matching the glob. without a glob, uses the \
+                     paths listed under [repos] in the config"
+    )]
+    pub repos: Option<String>,
 
     #[arg(
-        long = "add-remote",
-        aliases = ["radd"],
-        help = "EXPERIMENTAL: same as git remote add (remote name is 'origin' for now)"
+        long = "all-repos",
+        help = "runs meow in every git repo beneath the current directory"
     )]
-    pub addremote: Option<String>,
+    pub allrepos: bool,
 
     #[arg(
-        long = "remove-remote",
-        aliases = ["rrm"],
-        help = "EXPERIMENTAL: same as git remote remove (remote name is 'origin' for now)"
+        long = "jobs",
+        short = 'j',
+        value_name = "n",
+        default_value_t = 4,
+        help = "number of repos processed at once with --repos or --all-repos"
     )]
-    pub removeremote: bool,
+    pub jobs: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meow::Cause;
+
+    fn argv(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    fn rewrite(line: &str) -> (String, Option<String>) {
+        let (rewritten, replaced) = replacelegacyflags(argv(line));
+        let rewritten: Vec<String> = rewritten
+            .into_iter()
+            .map(|a| a.into_string().unwrap())
+            .collect();
+        (rewritten.join(" "), replaced.map(|(old, _)| old))
+    }
+
+    // a suggested `meow ...` command, with its placeholders filled in
+    fn parses(command: &str) -> bool {
+        let filled = command
+            .replace("<branch>", "feature")
+            .replace("<name>", "origin")
+            .replace("<url>", "git@example.com:me/repo.git")
+            .replace("<message>", "msg")
+            .replace("[files]", "src/");
+        Args::parsecommandline(argv(&filled)).is_ok()
+    }
+
+    #[test]
+    fn legacyflags() {
+        assert_eq!(rewrite("meow -p"), ("meow push".into(), Some("-p".into())));
+        assert_eq!(rewrite("meow -p -f").0, "meow push -f");
+        assert_eq!(rewrite("meow -c -d msg").0, "meow commit -d msg");
+        assert_eq!(
+            rewrite("meow --stage -a a.rs b.rs").0,
+            "meow stage a.rs b.rs"
+        );
+        assert_eq!(
+            rewrite("meow --add-remote=git@host:r.git").0,
+            "meow remote add origin git@host:r.git"
+        );
+        assert_eq!(rewrite("meow --rrm").0, "meow remote remove origin");
+        assert_eq!(
+            rewrite("meow --remove-remote upstream").0,
+            "meow remote remove upstream"
+        );
+    }
+
+    #[test]
+    fn bundledlegacyflags() {
+        assert_eq!(
+            rewrite("meow -pf"),
+            ("meow push -f".into(), Some("-p".into()))
+        );
+        assert_eq!(rewrite("meow -fp").0, "meow push -f");
+        assert_eq!(rewrite("meow -dpff").0, "meow push -dff");
+        assert_eq!(rewrite("meow -sa a.rs").0, "meow stage a.rs");
+        // letters after -u are its value, not flags
+        assert_eq!(rewrite("meow -uprod msg"), ("meow -uprod msg".into(), None));
+    }
+
+    #[test]
+    fn leavescurrentflagsalone() {
+        for line in [
+            "meow msg",
+            "meow -f msg",
+            "meow status",
+            "meow --status",
+            "meow --unstage",
+            "meow push -f",
+            "meow commit -- -p",
+            "meow -- -p",
+        ] {
+            assert_eq!(rewrite(line), (line.to_string(), None));
+        }
+    }
+
+    #[test]
+    fn suggestedcommandsparse() {
+        for (_, _, suggestion) in LEGACYFLAGS {
+            assert!(parses(suggestion), "{}", suggestion);
+        }
+
+        let causes = [
+            Cause::AuthFailed,
+            Cause::Rejected,
+            Cause::NotARepo,
+            Cause::PathspecMismatch,
+            Cause::NetworkUnreachable,
+            Cause::LockHeld,
+            Cause::NoUpstream,
+            Cause::RemoteExists,
+            Cause::NoSuchRemote,
+            Cause::NothingToCommit,
+            Cause::HookRejected(String::new()),
+            Cause::IdentityNotConfigured,
+            Cause::MergeInProgress,
+            Cause::TagExists,
+            Cause::SigningFailed,
+            Cause::UnpublishedSubmodule,
+            Cause::InvalidConfig,
+            Cause::StageFailed(String::new()),
+            Cause::SecretsFound,
+            Cause::LargeFile,
+            Cause::ProtectedBranch,
+            Cause::ForceNotConfirmed,
+            Cause::RestoreNotConfirmed,
+            Cause::Unknown,
+        ];
+        let commands: Vec<&str> = causes
+            .iter()
+            .filter_map(Cause::hint)
+            .flat_map(|hint| hint.split('`').skip(1).step_by(2))
+            .filter(|quoted| quoted.starts_with("meow "))
+            .collect();
+        assert!(commands.contains(&"meow push -u <branch>"));
+        for command in commands {
+            assert!(parses(command), "{}", command);
+        }
+    }
 }
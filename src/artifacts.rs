@@ -0,0 +1,281 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::gitoutput,
+    loggers::*,
+};
+use console::style;
+use dialoguer::Confirm;
+use glob::Pattern;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+pub struct Ecosystem {
+    pub name: &'static str,
+    /// files in the repository root that mark the ecosystem, globs allowed
+    pub markers: &'static [&'static str],
+    /// .gitignore patterns for its build output and dependencies
+    pub patterns: &'static [&'static str],
+}
+
+pub const ECOSYSTEMS: &[Ecosystem] = &[
+    Ecosystem {
+        name: "rust",
+        markers: &["Cargo.toml"],
+        patterns: &["target/"],
+    },
+    Ecosystem {
+        name: "node",
+        markers: &["package.json"],
+        patterns: &["node_modules/", ".next/", ".nuxt/", ".turbo/", "coverage/"],
+    },
+    Ecosystem {
+        name: "python",
+        markers: &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+        ],
+        patterns: &[
+            "__pycache__/",
+            "*.pyc",
+            ".venv/",
+            "venv/",
+            "*.egg-info/",
+            ".pytest_cache/",
+            ".mypy_cache/",
+        ],
+    },
+    Ecosystem {
+        name: "go",
+        markers: &["go.mod"],
+        patterns: &["bin/"],
+    },
+    Ecosystem {
+        name: "maven",
+        markers: &["pom.xml"],
+        patterns: &["target/"],
+    },
+    Ecosystem {
+        name: "gradle",
+        markers: &["build.gradle", "build.gradle.kts", "settings.gradle"],
+        patterns: &["build/", ".gradle/"],
+    },
+    Ecosystem {
+        name: "dotnet",
+        markers: &["*.sln", "*.csproj", "*.fsproj"],
+        patterns: &["bin/", "obj/"],
+    },
+    Ecosystem {
+        name: "elixir",
+        markers: &["mix.exs"],
+        patterns: &["_build/", "deps/"],
+    },
+    Ecosystem {
+        name: "zig",
+        markers: &["build.zig"],
+        patterns: &["zig-out/", ".zig-cache/"],
+    },
+    Ecosystem {
+        name: "cmake",
+        markers: &["CMakeLists.txt"],
+        patterns: &["build/", "CMakeFiles/"],
+    },
+];
+
+// os clutter, suggested whatever the ecosystem
+const ALWAYS: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+pub struct Artifact {
+    /// the untracked path as git reports it
+    pub path: String,
+    pub pattern: &'static str,
+}
+
+fn hasmarker(reporoot: &Path, marker: &str) -> bool {
+    if !marker.contains('*') {
+        return reporoot.join(marker).exists();
+    }
+
+    let Ok(pattern) = Pattern::new(marker) else {
+        return false;
+    };
+    fs::read_dir(reporoot).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| pattern.matches(&entry.file_name().to_string_lossy()))
+    })
+}
+
+/// ecosystems whose marker files are in the repository root
+pub fn detectecosystems(reporoot: &Path) -> Vec<&'static Ecosystem> {
+    ECOSYSTEMS
+        .iter()
+        .filter(|e| e.markers.iter().any(|m| hasmarker(reporoot, m)))
+        .collect()
+}
+
+// whether any component of `path` matches the gitignore-style `pattern`
+fn matchespattern(path: &str, pattern: &str) -> bool {
+    let isdir = path.ends_with('/');
+    let (name, dironly) = match pattern.strip_suffix('/') {
+        Some(name) => (name, true),
+        None => (pattern, false),
+    };
+    let Ok(glob) = Pattern::new(name) else {
+        return false;
+    };
+
+    let components: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    components.iter().enumerate().any(|(i, component)| {
+        let last = i == components.len() - 1;
+        // a dir-only pattern can't match the final component of a file
+        glob.matches(component) && (!dironly || !last || isdir)
+    })
+}
+
+/// untracked paths `git add .` would stage that look like build output,
+/// dependencies or os clutter
+pub fn findartifacts(reporoot: &Path) -> MeowResult<Vec<Artifact>> {
+    let mut patterns: Vec<&'static str> = detectecosystems(reporoot)
+        .iter()
+        .flat_map(|e| e.patterns.iter().copied())
+        .collect();
+    patterns.extend(ALWAYS);
+    patterns.dedup();
+
+    let untracked = gitoutput(
+        reporoot,
+        &["ls-files", "--others", "--exclude-standard", "--directory"],
+    )?;
+
+    Ok(untracked
+        .lines()
+        .filter_map(|path| {
+            patterns
+                .iter()
+                .find(|pattern| matchespattern(path, pattern))
+                .map(|pattern| Artifact {
+                    path: path.to_string(),
+                    pattern,
+                })
+        })
+        .collect())
+}
+
+fn addtogitignore(reporoot: &Path, patterns: &[&str]) -> MeowResult<()> {
+    let path = reporoot.join(".gitignore");
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+
+    let mut contents = String::new();
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        contents.push('\n');
+    }
+    for pattern in patterns {
+        contents.push_str(pattern);
+        contents.push('\n');
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| {
+            MeowError::pipeline(
+                Stage::Add,
+                Cause::Unknown,
+                format!("could not update .gitignore: {}", e),
+            )
+        })
+}
+
+/// warns about untracked build artifacts before `git add .` and offers to
+/// ignore them
+pub fn checkartifacts(reporoot: &Path, dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    debug("checking untracked files for build artifacts", verbose);
+    let artifacts = findartifacts(reporoot)?;
+    if artifacts.is_empty() {
+        debug("no build artifacts found", verbose);
+        return Ok(());
+    }
+
+    detail("    these look like build output and would be staged:");
+    for artifact in &artifacts {
+        detail(&format!(
+            "      {} (matches {})",
+            style(&artifact.path).bold(),
+            artifact.pattern
+        ));
+    }
+
+    let mut patterns: Vec<&str> = artifacts.iter().map(|a| a.pattern).collect();
+    patterns.sort();
+    patterns.dedup();
+
+    if *dryrun {
+        info(&format!(
+            "    would offer to add {} to .gitignore",
+            patterns.join(" ")
+        ));
+        return Ok(());
+    }
+
+    let ignore = Confirm::new()
+        .with_prompt(format!(
+            "add {} to .gitignore and skip them?",
+            patterns.join(" ")
+        ))
+        .default(true)
+        .interact()
+        .unwrap_or(false);
+    if !ignore {
+        info("    staging them anyway");
+        return Ok(());
+    }
+
+    addtogitignore(reporoot, &patterns)?;
+    success("    updated .gitignore");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn dironlypatterns() {
+        assert!(matchespattern("target/", "target/"));
+        assert!(matchespattern("crates/cli/target/", "target/"));
+        assert!(matchespattern("target/debug/meow", "target/"));
+        // a file called target isn't a build directory
+        assert!(!matchespattern("target", "target/"));
+        assert!(!matchespattern("docs/target", "target/"));
+        assert!(!matchespattern("targets/", "target/"));
+    }
+
+    #[test]
+    fn globpatterns() {
+        assert!(matchespattern("src/__pycache__/", "__pycache__/"));
+        assert!(matchespattern("lib/util.pyc", "*.pyc"));
+        assert!(matchespattern("meow.egg-info/", "*.egg-info/"));
+        assert!(matchespattern("assets/.DS_Store", ".DS_Store"));
+        assert!(!matchespattern("src/util.py", "*.pyc"));
+    }
+
+    #[test]
+    fn ecosystemsfrommarkers() {
+        let root = env::temp_dir().join(format!("meow-artifacts-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(root.join("app.csproj"), "").unwrap();
+
+        let names: Vec<&str> = detectecosystems(&root).iter().map(|e| e.name).collect();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(names, ["rust", "dotnet"]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::gitoutput,
+    loggers::*,
+};
+use dialoguer::Confirm;
+use glob::Pattern;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BranchesConfig {
+    /// branch names or globs that need confirmation to commit or push to
+    pub protected: Vec<String>,
+}
+
+impl Default for BranchesConfig {
+    fn default() -> Self {
+        Self {
+            protected: vec![
+                String::from("main"),
+                String::from("master"),
+                String::from("release/*"),
+            ],
+        }
+    }
+}
+
+impl BranchesConfig {
+    pub fn isprotected(&self, branch: &str) -> bool {
+        self.protected
+            .iter()
+            .any(|p| Pattern::new(p).is_ok_and(|p| p.matches(branch)))
+    }
+}
+
+pub fn currentbranch(repo: &Path) -> Option<String> {
+    gitoutput(repo, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .ok()
+        .map(|b| b.trim().to_string())
+}
+
+/// the remote branch a push would update: the -u branch if given, else the
+/// branch's push target, else a branch of the same name
+pub fn pushtarget(repo: &Path, upstream: Option<&str>) -> Option<String> {
+    if let Some(upstream) = upstream {
+        return Some(upstream.to_string());
+    }
+
+    match gitoutput(repo, &["rev-parse", "--abbrev-ref", "@{push}"]) {
+        // origin/main
+        Ok(target) => target
+            .trim()
+            .split_once('/')
+            .map(|(_, branch)| branch.to_string()),
+        Err(_) => currentbranch(repo),
+    }
+}
+
+fn protectederror(message: String) -> MeowError {
+    MeowError::pipeline(Stage::Branch, Cause::ProtectedBranch, message)
+}
+
+/// asks before committing on or pushing to a protected branch, and refuses
+/// force pushes to one unless `forceprotected` is set
+pub fn guardbranches(
+    repo: &Path,
+    config: &BranchesConfig,
+    commit: bool,
+    push: Option<(Option<&str>, u8)>,
+    forceprotected: &bool,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    debug("checking for protected branches", verbose);
+    let mut actions = Vec::new();
+
+    let branch = currentbranch(repo);
+    if commit
+        && let Some(branch) = branch.as_deref()
+        && config.isprotected(branch)
+    {
+        actions.push(format!("commit on {}", branch));
+    }
+
+    if let Some((upstream, force)) = push
+        && let Some(target) = pushtarget(repo, upstream)
+        && config.isprotected(&target)
+    {
+        if force > 0 && !*forceprotected {
+            return Err(protectederror(format!(
+                "refusing to force push to protected branch `{}`",
+                target
+            )));
+        }
+        actions.push(format!(
+            "{}push to {}",
+            if force > 0 { "force " } else { "" },
+            target
+        ));
+    }
+
+    if actions.is_empty() {
+        debug("no protected branches involved", verbose);
+        return Ok(());
+    }
+
+    let prompt = format!("{} (protected)?", actions.join(" and "));
+    if *dryrun {
+        info(&format!("    would ask: {}", prompt));
+        return Ok(());
+    }
+
+    let confirmed = Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if confirmed {
+        Ok(())
+    } else {
+        Err(protectederror(format!(
+            "not continuing: {} was not confirmed",
+            actions.join(" and ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaultprotected() {
+        let config = BranchesConfig::default();
+        assert!(config.isprotected("main"));
+        assert!(config.isprotected("master"));
+        assert!(config.isprotected("release/1.2"));
+        assert!(!config.isprotected("feature/main"));
+        assert!(!config.isprotected("mainline"));
+        assert!(!config.isprotected("release"));
+    }
+
+    #[test]
+    fn protectedglobs() {
+        let config = BranchesConfig {
+            protected: vec![String::from("prod-*"), String::from("[")],
+        };
+        assert!(config.isprotected("prod-eu"));
+        assert!(!config.isprotected("main"));
+        // an invalid glob protects nothing rather than everything
+        assert!(!config.isprotected("["));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::args::Args;
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::generate;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+}
+
+impl Shell {
+    fn generator(&self) -> clap_complete::Shell {
+        match self {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+            Shell::Elvish => clap_complete::Shell::Elvish,
+        }
+    }
+}
+
+/// writes the completion script for `shell`
+pub fn printcompletions(shell: Shell) -> io::Result<()> {
+    let mut cmd = Args::command();
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    generate(shell.generator(), &mut cmd, name, &mut script);
+    io::stdout().write_all(&script)
+}
+
+/// writes a roff man page generated from the arguments
+pub fn printmanpage() -> io::Result<()> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(Args::command()).render(&mut page)?;
+    io::stdout().write_all(&page)
+}
+
+/// writes meow.1 and a page for each command, like meow-push.1, into `dir`
+pub fn writemanpages(dir: &Path) -> io::Result<()> {
+    clap_mangen::generate_to(Args::command(), dir)
+}
@@ -0,0 +1,128 @@
+use crate::{
+    branches::BranchesConfig,
+    error::{Cause, MeowError, MeowResult, Stage},
+    largefiles::FilesConfig,
+    logfile::LogConfig,
+    stages::CustomStage,
+    theme::{OutputConfig, Theme},
+};
+use homedir::my_home;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+use toml::{Table, Value};
+
+pub const REPOCONFIGFILE: &str = ".meow.toml";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub release: ReleaseConfig,
+    pub repos: ReposConfig,
+    pub files: FilesConfig,
+    pub branches: BranchesConfig,
+    /// custom stages slotted into the pipeline, in order
+    pub stages: Vec<CustomStage>,
+    /// the stages came from the repository's .meow.toml, so anyone who could
+    /// commit to it wrote them. see `trust::trustedstages`
+    #[serde(skip)]
+    pub repostages: bool,
+    /// names that expand to a meow invocation, e.g. `wip = "-a . 'wip'"`. only
+    /// read from the global config
+    pub aliases: BTreeMap<String, String>,
+    pub output: OutputConfig,
+    pub theme: Theme,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ReposConfig {
+    /// repositories used by `--repos` when no glob is given
+    pub paths: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ReleaseConfig {
+    /// extra manifest files (relative to the repo root) whose version is bumped
+    /// alongside Cargo.toml
+    pub manifests: Vec<String>,
+    #[serde(rename = "tag-prefix")]
+    pub tagprefix: String,
+    /// always update CHANGELOG.md when releasing
+    pub changelog: bool,
+}
+
+impl Default for ReleaseConfig {
+    fn default() -> Self {
+        Self {
+            manifests: Vec::new(),
+            tagprefix: String::from("v"),
+            changelog: false,
+        }
+    }
+}
+
+/// loads ~/.config/meow/config.toml, then merges the repo's .meow.toml over it.
+/// aliases only come from the global config: a cloned repository could
+/// otherwise turn ordinary words into any meow invocation
+pub fn loadconfig(reporoot: Option<&Path>) -> MeowResult<Config> {
+    let mut merged = Table::new();
+    let mut repostages = false;
+
+    if let Ok(Some(home)) = my_home() {
+        let globalpath = home.join(".config").join("meow").join("config.toml");
+        if let Some(table) = readtable(&globalpath)? {
+            mergetables(&mut merged, table);
+        }
+    }
+
+    if let Some(root) = reporoot
+        && let Some(mut table) = readtable(&root.join(REPOCONFIGFILE))?
+    {
+        table.remove("aliases");
+        repostages = table.contains_key("stages");
+        mergetables(&mut merged, table);
+    }
+
+    let mut config = Config::deserialize(Value::Table(merged)).map_err(|e| {
+        MeowError::pipeline(
+            Stage::Setup,
+            Cause::InvalidConfig,
+            format!("invalid meow config: {}", e.message()),
+        )
+    })?;
+    config.repostages = repostages;
+    Ok(config)
+}
+
+fn readtable(path: &Path) -> MeowResult<Option<Table>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let invalid =
+        |message: String| MeowError::pipeline(Stage::Setup, Cause::InvalidConfig, message);
+    let contents = fs::read_to_string(path)
+        .map_err(|e| invalid(format!("could not read {}: {}", path.display(), e)))?;
+    contents.parse::<Table>().map(Some).map_err(|e| {
+        invalid(format!(
+            "could not parse {}: {}",
+            path.display(),
+            e.message()
+        ))
+    })
+}
+
+fn mergetables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(basetable)), Value::Table(overlaytable)) => {
+                mergetables(basetable, overlaytable)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Setup,
+    Add,
+    Unstage,
+    Restore,
+    Files,
+    Branch,
+    Commit,
+    Tag,
+    Push,
+    Remote,
+    Release,
+    Changelog,
+    Submodules,
+    Secrets,
+    Custom,
+    Status,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Setup => "setup",
+            Stage::Add => "stage",
+            Stage::Unstage => "unstage",
+            Stage::Restore => "restore",
+            Stage::Files => "files",
+            Stage::Branch => "branch",
+            Stage::Commit => "commit",
+            Stage::Tag => "tag",
+            Stage::Push => "push",
+            Stage::Remote => "remote",
+            Stage::Release => "release",
+            Stage::Changelog => "changelog",
+            Stage::Submodules => "submodules",
+            Stage::Secrets => "secrets",
+            Stage::Custom => "custom",
+            Stage::Status => "status",
+        }
+    }
+
+    fn failure(&self) -> &'static str {
+        match self {
+            Stage::Setup => "could not run git",
+            Stage::Add => "could not stage files",
+            Stage::Unstage => "could not unstage files",
+            Stage::Restore => "could not restore files",
+            Stage::Files => "could not check staged files",
+            Stage::Branch => "could not check protected branches",
+            Stage::Commit => "could not commit files",
+            Stage::Tag => "could not create tag",
+            Stage::Push => "could not push to remote",
+            Stage::Remote => "could not update remote",
+            Stage::Release => "could not release",
+            Stage::Changelog => "could not update changelog",
+            Stage::Submodules => "could not update submodules",
+            Stage::Secrets => "could not scan for secrets",
+            Stage::Custom => "a custom stage failed",
+            Stage::Status => "could not read status",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cause {
+    AuthFailed,
+    Rejected,
+    NotARepo,
+    PathspecMismatch,
+    NetworkUnreachable,
+    LockHeld,
+    NoUpstream,
+    RemoteExists,
+    NoSuchRemote,
+    NothingToCommit,
+    HookRejected(String),
+    IdentityNotConfigured,
+    MergeInProgress,
+    TagExists,
+    SigningFailed,
+    UnpublishedSubmodule,
+    InvalidConfig,
+    StageFailed(String),
+    SecretsFound,
+    LargeFile,
+    ProtectedBranch,
+    ForceNotConfirmed,
+    RestoreNotConfirmed,
+    DetachedHead,
+    Unknown,
+}
+
+impl Cause {
+    /// classifies a failed git command from its stderr
+    pub fn classify(stderr: &str) -> Cause {
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| stderr.contains(p));
+
+        if matches(&["not a git repository"]) {
+            Cause::NotARepo
+        } else if matches(&[
+            "Authentication failed",
+            "Permission denied (publickey",
+            "could not read Username",
+            "could not read Password",
+            "The requested URL returned error: 403",
+            "The requested URL returned error: 401",
+        ]) {
+            Cause::AuthFailed
+        } else if matches(&[
+            "Could not resolve host",
+            "Could not resolve hostname",
+            "Connection refused",
+            "Connection timed out",
+            "Network is unreachable",
+            "Operation timed out",
+        ]) {
+            Cause::NetworkUnreachable
+        } else if matches(&[
+            "[rejected]",
+            "[remote rejected]",
+            "non-fast-forward",
+            "stale info",
+        ]) {
+            Cause::Rejected
+        } else if matches(&["did not match any files", "pathspec"]) {
+            Cause::PathspecMismatch
+        } else if matches(&[".lock': File exists", "index.lock", "Unable to create"]) {
+            Cause::LockHeld
+        } else if matches(&["has no upstream branch", "no upstream configured"]) {
+            Cause::NoUpstream
+        } else if matches(&["already exists"]) && stderr.contains("remote") {
+            Cause::RemoteExists
+        } else if matches(&["No such remote", "No configured push destination"]) {
+            Cause::NoSuchRemote
+        } else if matches(&["gpg failed", "failed to sign"]) {
+            Cause::SigningFailed
+        } else if matches(&["Please tell me who you are", "empty ident name"]) {
+            Cause::IdentityNotConfigured
+        } else if matches(&["unmerged files", "not concluded your merge"]) {
+            Cause::MergeInProgress
+        } else {
+            Cause::Unknown
+        }
+    }
+
+    pub fn describe(&self) -> Option<String> {
+        let description = match self {
+            Cause::AuthFailed => "authentication failed",
+            Cause::Rejected => "rejected by the remote",
+            Cause::NotARepo => "not a git repository",
+            Cause::PathspecMismatch => "files not found",
+            Cause::NetworkUnreachable => "the remote is unreachable",
+            Cause::LockHeld => "the repository is locked by another git process",
+            Cause::NoUpstream => "the branch has no upstream",
+            Cause::RemoteExists => "the remote already exists",
+            Cause::NoSuchRemote => "the remote does not exist",
+            Cause::NothingToCommit => "nothing to commit",
+            Cause::HookRejected(hook) => return Some(format!("the {} hook failed", hook)),
+            Cause::IdentityNotConfigured => "git identity is not configured",
+            Cause::MergeInProgress => "a merge is in progress with unresolved conflicts",
+            Cause::TagExists => "the tag already exists",
+            Cause::SigningFailed => "signing failed",
+            Cause::UnpublishedSubmodule => "a submodule commit hasn't been pushed",
+            Cause::InvalidConfig => "invalid config",
+            Cause::StageFailed(name) => return Some(format!("`{}` exited unsuccessfully", name)),
+            Cause::SecretsFound => "the staged changes contain likely secrets",
+            Cause::LargeFile => "large or binary files are staged",
+            Cause::ProtectedBranch => "the branch is protected",
+            Cause::ForceNotConfirmed => "the force push was not confirmed",
+            Cause::RestoreNotConfirmed => "discarding the changes was not confirmed",
+            Cause::DetachedHead => "HEAD is detached, so there's no branch to push",
+            Cause::Unknown => return None,
+        };
+        Some(description.to_string())
+    }
+
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Cause::AuthFailed => Some(
+                "check your credentials or ssh key (`ssh -T git@<host>`) and that you can access the repository",
+            ),
+            Cause::Rejected => Some(
+                "the remote has commits you don't have. pull them first, or use -f to force with lease",
+            ),
+            Cause::NotARepo => {
+                Some("run meow inside a git repository, or create one with `git init`")
+            }
+            Cause::PathspecMismatch => Some("check that the paths passed to -a exist"),
+            Cause::NetworkUnreachable => {
+                Some("check your connection and the remote url (`git remote -v`)")
+            }
+            Cause::LockHeld => Some(
+                "wait for the other git process to finish. if none is running, delete .git/index.lock",
+            ),
+            Cause::NoUpstream => Some("set one with `meow push -u <branch>`"),
+            Cause::RemoteExists => Some("remove it first with `meow remote remove <name>`"),
+            Cause::NoSuchRemote => Some("add one with `meow remote add <name> <url>`"),
+            Cause::NothingToCommit => Some("are there any staged changes?"),
+            Cause::HookRejected(_) => {
+                Some("fix the issues reported above, or bypass hooks with `git commit --no-verify`")
+            }
+            Cause::IdentityNotConfigured => Some(
+                "set it with `git config --global user.name` and `git config --global user.email`",
+            ),
+            Cause::MergeInProgress => Some("resolve the conflicts and stage the files first"),
+            Cause::TagExists => Some("use --force-tag to overwrite it"),
+            Cause::SigningFailed => {
+                Some("check that gpg is installed and `user.signingkey` is set")
+            }
+            Cause::UnpublishedSubmodule => {
+                Some("push the submodules first, or rerun meow to be offered to")
+            }
+            Cause::InvalidConfig => Some("check ~/.config/meow/config.toml and .meow.toml"),
+            Cause::StageFailed(_) => Some(
+                "fix the issues reported above, or change its `on-failure` policy in .meow.toml",
+            ),
+            Cause::SecretsFound => Some(
+                "unstage them, list false positives in .meow-allowlist, or pass --allow-secrets",
+            ),
+            Cause::LargeFile => {
+                Some("unstage them, track them with git lfs, or raise files.max-size in .meow.toml")
+            }
+            Cause::ProtectedBranch => Some(
+                "work on another branch, or confirm when asked. force pushes also need --force-protected",
+            ),
+            Cause::ForceNotConfirmed => {
+                Some("pull the remote commits first, or confirm in a terminal")
+            }
+            Cause::RestoreNotConfirmed => Some("rerun meow in a terminal to confirm"),
+            Cause::DetachedHead => Some(
+                "check out the branch the submodule tracks (`git -C <path> switch <branch>`) and rerun meow",
+            ),
+            Cause::Unknown => None,
+        }
+    }
+
+    /// process exit code, stable per cause so scripts can branch on it
+    pub fn exitcode(&self) -> i32 {
+        match self {
+            Cause::Unknown => 1,
+            Cause::NotARepo => 3,
+            Cause::AuthFailed => 4,
+            Cause::NetworkUnreachable => 5,
+            Cause::Rejected => 6,
+            Cause::LockHeld => 7,
+            Cause::PathspecMismatch => 8,
+            Cause::NothingToCommit => 9,
+            Cause::HookRejected(_) => 10,
+            Cause::IdentityNotConfigured => 11,
+            Cause::MergeInProgress => 12,
+            Cause::NoUpstream => 13,
+            Cause::RemoteExists | Cause::NoSuchRemote => 14,
+            Cause::TagExists => 15,
+            Cause::SigningFailed => 16,
+            Cause::UnpublishedSubmodule => 17,
+            Cause::InvalidConfig => 18,
+            Cause::StageFailed(_) => 19,
+            Cause::SecretsFound => 20,
+            Cause::LargeFile => 21,
+            Cause::ProtectedBranch => 22,
+            Cause::ForceNotConfirmed => 23,
+            Cause::RestoreNotConfirmed => 24,
+            Cause::DetachedHead => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MeowError {
+    /// a git command ran and exited unsuccessfully
+    Command {
+        stage: Stage,
+        command: String,
+        exitcode: Option<i32>,
+        stdout: String,
+        stderr: String,
+        cause: Cause,
+    },
+    /// git could not be started at all
+    Spawn {
+        stage: Stage,
+        command: String,
+        reason: String,
+    },
+    /// meow itself refused to continue
+    Pipeline {
+        stage: Stage,
+        cause: Cause,
+        message: String,
+    },
+}
+
+impl MeowError {
+    pub fn pipeline(stage: Stage, cause: Cause, message: impl Into<String>) -> MeowError {
+        MeowError::Pipeline {
+            stage,
+            cause,
+            message: message.into(),
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        match self {
+            MeowError::Command { stage, .. }
+            | MeowError::Spawn { stage, .. }
+            | MeowError::Pipeline { stage, .. } => *stage,
+        }
+    }
+
+    pub fn cause(&self) -> &Cause {
+        match self {
+            MeowError::Command { cause, .. } | MeowError::Pipeline { cause, .. } => cause,
+            MeowError::Spawn { .. } => &Cause::Unknown,
+        }
+    }
+
+    pub fn withstage(mut self, newstage: Stage) -> MeowError {
+        match &mut self {
+            MeowError::Command { stage, .. }
+            | MeowError::Spawn { stage, .. }
+            | MeowError::Pipeline { stage, .. } => *stage = newstage,
+        }
+        self
+    }
+
+    pub fn withcause(mut self, newcause: Cause) -> MeowError {
+        match &mut self {
+            MeowError::Command { cause, .. } | MeowError::Pipeline { cause, .. } => {
+                *cause = newcause
+            }
+            MeowError::Spawn { .. } => (),
+        }
+        self
+    }
+
+    pub fn hint(&self) -> Option<&'static str> {
+        self.cause().hint()
+    }
+
+    pub fn exitcode(&self) -> i32 {
+        self.cause().exitcode()
+    }
+}
+
+impl fmt::Display for MeowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeowError::Command {
+                stage,
+                cause,
+                stderr,
+                ..
+            } => match cause.describe() {
+                Some(description) => write!(f, "{}: {}", stage.failure(), description),
+                None => match stderr.lines().map(str::trim).find(|l| !l.is_empty()) {
+                    Some(line) => write!(f, "{}: {}", stage.failure(), line),
+                    None => write!(f, "{}", stage.failure()),
+                },
+            },
+            MeowError::Spawn {
+                command, reason, ..
+            } => write!(f, "failed to execute `{}`: {}", command, reason),
+            MeowError::Pipeline { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for MeowError {}
+
+pub type MeowResult<T> = Result<T, MeowError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commanderror(stderr: &str) -> MeowError {
+        MeowError::Command {
+            stage: Stage::Push,
+            command: String::from("git push"),
+            exitcode: Some(1),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            cause: Cause::classify(stderr),
+        }
+    }
+
+    #[test]
+    fn classifygitstderr() {
+        let cases = [
+            (
+                "fatal: not a git repository (or any of the parent directories): .git",
+                Cause::NotARepo,
+            ),
+            (
+                "fatal: Authentication failed for 'https://github.com/o/r.git/'",
+                Cause::AuthFailed,
+            ),
+            (
+                "ssh: Could not resolve hostname github.com: Name or service not known",
+                Cause::NetworkUnreachable,
+            ),
+            (
+                " ! [rejected]        main -> main (fetch first)",
+                Cause::Rejected,
+            ),
+            (
+                "error: pathspec 'nope' did not match any file(s) known to git",
+                Cause::PathspecMismatch,
+            ),
+            (
+                "fatal: Unable to create '/r/.git/index.lock': File exists.",
+                Cause::LockHeld,
+            ),
+            (
+                "fatal: The current branch dev has no upstream branch.",
+                Cause::NoUpstream,
+            ),
+            ("error: remote origin already exists.", Cause::RemoteExists),
+            ("error: No such remote: 'origin'", Cause::NoSuchRemote),
+            ("error: gpg failed to sign the data", Cause::SigningFailed),
+            (
+                "*** Please tell me who you are.",
+                Cause::IdentityNotConfigured,
+            ),
+            (
+                "error: Committing is not possible because you have unmerged files.",
+                Cause::MergeInProgress,
+            ),
+            ("fatal: something new", Cause::Unknown),
+        ];
+        for (stderr, cause) in cases {
+            assert_eq!(Cause::classify(stderr), cause, "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn tagexistsisnotaremote() {
+        assert_eq!(
+            Cause::classify("fatal: tag 'v1.0.0' already exists"),
+            Cause::Unknown
+        );
+    }
+
+    #[test]
+    fn displaydescribedcause() {
+        assert_eq!(
+            commanderror("fatal: Authentication failed").to_string(),
+            "could not push to remote: authentication failed"
+        );
+    }
+
+    #[test]
+    fn displayunknownshowsfirstline() {
+        assert_eq!(
+            commanderror("\n  fatal: something new\nmore detail").to_string(),
+            "could not push to remote: fatal: something new"
+        );
+        assert_eq!(commanderror("").to_string(), "could not push to remote");
+    }
+
+    #[test]
+    fn withstageandcause() {
+        let error = commanderror("fatal: something new")
+            .withstage(Stage::Tag)
+            .withcause(Cause::TagExists);
+        assert_eq!(error.stage(), Stage::Tag);
+        assert_eq!(*error.cause(), Cause::TagExists);
+        assert_eq!(error.exitcode(), 15);
+        assert_eq!(error.hint(), Some("use --force-tag to overwrite it"));
+    }
+
+    #[test]
+    fn spawnerrorsareunknown() {
+        let error = MeowError::Spawn {
+            stage: Stage::Setup,
+            command: String::from("git status"),
+            reason: String::from("No such file or directory"),
+        }
+        .withcause(Cause::Rejected);
+        assert_eq!(*error.cause(), Cause::Unknown);
+        assert_eq!(error.exitcode(), 1);
+        assert_eq!(
+            error.to_string(),
+            "failed to execute `git status`: No such file or directory"
+        );
+    }
+
+    #[test]
+    fn pipelineerrorsshowtheirmessage() {
+        let error = MeowError::pipeline(Stage::Setup, Cause::InvalidConfig, "bad config");
+        assert_eq!(error.to_string(), "bad config");
+        assert_eq!(error.exitcode(), 18);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::gitoutput,
+    loggers::*,
+};
+use console::style;
+use dialoguer::Confirm;
+use std::path::Path;
+
+pub struct DiscardedCommit {
+    pub hash: String,
+    pub author: String,
+    pub subject: String,
+}
+
+// `git version 2.39.5` -> (2, 39)
+fn parseversion(output: &str) -> (u32, u32) {
+    let mut parts = output
+        .split_whitespace()
+        .nth(2)
+        .unwrap_or_default()
+        .split('.')
+        .map(|p| p.parse::<u32>().unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+/// --force-if-includes needs git 2.30 or newer
+pub fn supportsifincludes(repo: &Path) -> bool {
+    gitoutput(repo, &["version"]).is_ok_and(|version| parseversion(&version) >= (2, 30))
+}
+
+/// the push flags for each -f level: with-lease, then --force. `ifincludes`
+/// adds --force-if-includes to a push with lease
+pub fn forceargs(repo: &Path, force: u8, ifincludes: bool, verbose: &u8) -> Vec<&'static str> {
+    match force {
+        0 => Vec::new(),
+        1 if ifincludes && supportsifincludes(repo) => {
+            debug(
+                "force and if-includes were specified, using force-with-lease and force-if-includes",
+                verbose,
+            );
+            vec!["--force-with-lease", "--force-if-includes"]
+        }
+        1 => {
+            if ifincludes {
+                emit(
+                    Level::Hint,
+                    "git is too old for --force-if-includes (2.30+), pushing with lease only",
+                );
+            } else {
+                debug("force was specified, using force-with-lease", verbose);
+            }
+            vec!["--force-with-lease"]
+        }
+        _ => {
+            debug("force was specified twice, using force", verbose);
+            vec!["--force"]
+        }
+    }
+}
+
+/// the remote-tracking ref a push would overwrite, if it exists locally
+pub fn remoteref(repo: &Path, upstream: Option<&str>) -> Option<String> {
+    let name = match upstream {
+        Some(upstream) => format!("refs/remotes/origin/{}", upstream),
+        None => gitoutput(repo, &["rev-parse", "--symbolic-full-name", "@{push}"])
+            .ok()?
+            .trim()
+            .to_string(),
+    };
+    gitoutput(repo, &["rev-parse", "--verify", "--quiet", &name])
+        .ok()
+        .map(|_| name)
+}
+
+/// commits on the remote-tracking ref that HEAD doesn't contain
+pub fn discardedcommits(repo: &Path, remoteref: &str) -> Vec<DiscardedCommit> {
+    parsediscarded(
+        &gitoutput(
+            repo,
+            &[
+                "log",
+                "--format=%h%x09%an%x09%s",
+                remoteref,
+                "--not",
+                "HEAD",
+            ],
+        )
+        .unwrap_or_default(),
+    )
+}
+
+// `<hash>\t<author>\t<subject>` lines from `git log`
+fn parsediscarded(log: &str) -> Vec<DiscardedCommit> {
+    log.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            Some(DiscardedCommit {
+                hash: parts.next()?.to_string(),
+                author: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// lists the remote commits a force push would discard and asks before going ahead
+pub fn confirmforcepush(
+    repo: &Path,
+    upstream: Option<&str>,
+    force: &u8,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    if *force == 0 {
+        return Ok(());
+    }
+
+    debug(
+        "checking which remote commits a force push would discard",
+        verbose,
+    );
+    let Some(remoteref) = remoteref(repo, upstream) else {
+        debug("no remote-tracking branch, nothing to discard", verbose);
+        return Ok(());
+    };
+    let discarded = discardedcommits(repo, &remoteref);
+    if discarded.is_empty() {
+        debug("the force push won't discard any remote commits", verbose);
+        return Ok(());
+    }
+
+    let shortref = remoteref.trim_start_matches("refs/remotes/");
+    error(&format!(
+        "    force pushing will discard {} commit(s) from {}:",
+        discarded.len(),
+        style(shortref).bold()
+    ));
+    for commit in &discarded {
+        detail(&format!(
+            "      {}  {}  {}",
+            style(&commit.hash).yellow(),
+            commit.author,
+            commit.subject
+        ));
+    }
+    if *force >= 2 {
+        detail("    commits pushed since the last fetch aren't listed and will be lost too");
+    }
+
+    if *dryrun {
+        info("    would ask for confirmation before force pushing");
+        return Ok(());
+    }
+
+    let confirmed = Confirm::new()
+        .with_prompt("discard these commits?")
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if confirmed {
+        Ok(())
+    } else {
+        Err(MeowError::pipeline(
+            Stage::Push,
+            Cause::ForceNotConfirmed,
+            "not force pushing: discarding the remote commits was not confirmed",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitversions() {
+        assert_eq!(parseversion("git version 2.39.5\n"), (2, 39));
+        assert_eq!(parseversion("git version 2.30.0.windows.1"), (2, 30));
+        assert_eq!(parseversion("git version 2.45.GIT"), (2, 45));
+        assert_eq!(parseversion("not git"), (0, 0));
+        assert!(parseversion("git version 2.29.2") < (2, 30));
+    }
+
+    #[test]
+    fn discardedfromlog() {
+        let log = "a1b2c3d\tAda Lovelace\tfix: tabs\tin subject\ne4f5a6b\tbob\t\n";
+        let commits = parsediscarded(log);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "a1b2c3d");
+        assert_eq!(commits[0].author, "Ada Lovelace");
+        assert_eq!(commits[0].subject, "fix: tabs\tin subject");
+        assert_eq!(commits[1].subject, "");
+        assert!(parsediscarded("").is_empty());
+    }
+
+    #[test]
+    fn forcelevels() {
+        assert!(forceargs(Path::new("."), 0, true, &0).is_empty());
+        assert_eq!(
+            forceargs(Path::new("."), 1, false, &0),
+            ["--force-with-lease"]
+        );
+        assert_eq!(forceargs(Path::new("."), 2, true, &0), ["--force"]);
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    forcepush::forceargs,
+    loggers::*,
+};
+use dialoguer::Confirm;
+use homedir::my_home;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    time::{Instant, SystemTime},
+};
+
+pub fn getrootdir() -> MeowResult<PathBuf> {
+    // git rev-parse --show-toplevel
+    let cwd = std::env::current_dir().map_err(|e| {
+        MeowError::pipeline(
+            Stage::Setup,
+            Cause::Unknown,
+            format!("could not read the current directory: {}", e),
+        )
+    })?;
+    let stdout = gitoutput(&cwd, &["rev-parse", "--show-toplevel"]).map_err(|e| {
+        if *e.cause() == Cause::NotARepo {
+            MeowError::pipeline(
+                Stage::Setup,
+                Cause::NotARepo,
+                "not a git repository. are you in the correct path?",
+            )
+        } else {
+            e
+        }
+    })?;
+
+    Ok(PathBuf::from(stdout.trim()))
+}
+
+pub fn getcleanroot(reporoot: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let homediropt = my_home()?;
+
+    let cleanroot = if let Some(homedir) = homediropt {
+        if reporoot.starts_with(&homedir) {
+            let relpath = reporoot.strip_prefix(&homedir)?;
+            format!("~/{}", relpath.display())
+        } else {
+            reporoot.to_string_lossy().into_owned()
+        }
+    } else {
+        reporoot.to_string_lossy().into_owned()
+    };
+
+    Ok(cleanroot)
+}
+
+pub(crate) fn createcommand<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec!["git"];
+    command.extend(args);
+
+    command
+}
+
+pub(crate) fn spawn(repopath: &Path, args: &[&str], stage: Stage) -> MeowResult<Output> {
+    let commandparts = createcommand(args);
+
+    let command = commandparts[0];
+    let commandargs = &commandparts[1..];
+
+    let mut cmd = Command::new(command);
+    cmd.args(commandargs);
+    cmd.current_dir(repopath);
+
+    cmd.output().map_err(|e| MeowError::Spawn {
+        stage,
+        command: commandparts.join(" "),
+        reason: e.to_string(),
+    })
+}
+
+pub(crate) fn commanderror(args: &[&str], output: &Output, stage: Stage) -> MeowError {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    MeowError::Command {
+        stage,
+        command: createcommand(args).join(" "),
+        exitcode: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        cause: Cause::classify(&stderr),
+        stderr,
+    }
+}
+
+// runs and prints a git command, returning its output whether or not it succeeded.
+// -vv also traces where and how it ran and everything it printed
+pub fn executecommand(repopath: &Path, args: &[&str], stage: Stage) -> MeowResult<Output> {
+    printcommand(&createcommand(args));
+
+    let verbose = verbosity();
+    trace(&format!("cwd: {}", repopath.display()), &verbose);
+    for (name, value) in env::vars().filter(|(name, _)| name.starts_with("GIT_")) {
+        trace(&format!("env: {}={}", name, value), &verbose);
+    }
+
+    let started = Instant::now();
+    let output = spawn(repopath, args, stage)?;
+
+    let exitcode = output.status.code().map_or_else(
+        || String::from("none (killed by a signal)"),
+        |c| c.to_string(),
+    );
+    trace(
+        &format!(
+            "exit code {} after {}ms",
+            exitcode,
+            started.elapsed().as_millis()
+        ),
+        &verbose,
+    );
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        trace(&format!("stdout: {}", line), &verbose);
+    }
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        trace(&format!("stderr: {}", line), &verbose);
+    }
+    Ok(output)
+}
+
+pub fn runcommand(repopath: &Path, args: &[&str], stage: Stage) -> MeowResult<Output> {
+    let o = executecommand(repopath, args, stage)?;
+    if o.status.success() {
+        Ok(o)
+    } else {
+        Err(commanderror(args, &o, stage))
+    }
+}
+
+// runs a read-only git query without printing it, returning stdout
+pub fn gitoutput(repopath: &Path, args: &[&str]) -> MeowResult<String> {
+    let output = spawn(repopath, args, Stage::Setup)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(commanderror(args, &output, Stage::Setup))
+    }
+}
+
+pub fn stageall(repopath: &Path, dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    debug("no files were specified, staging all", verbose);
+    let args = &["add", "."];
+
+    if *dryrun {
+        debug("debug was specified, not staging", verbose);
+        printcommand(args);
+        return Ok(());
+    }
+
+    let o = runcommand(repopath, args, Stage::Add)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+pub fn stage(repopath: &Path, files: &[String], dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    debug(&format!("files {:#?} were specified", files), verbose);
+    let mut args = vec!["add"];
+    args.extend(files.iter().map(String::as_str));
+
+    if *dryrun {
+        debug("debug was specified, not staging", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    let o = runcommand(repopath, &args, Stage::Add)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+pub fn hashead(repopath: &Path) -> bool {
+    gitoutput(repopath, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok()
+}
+
+pub fn unstage(repopath: &Path, files: &[String], dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    // there's nothing to restore from before the first commit
+    let mut args = if hashead(repopath) {
+        vec!["restore", "--staged", "--"]
+    } else {
+        debug("no commits yet, unstaging with rm --cached", verbose);
+        vec!["rm", "--cached", "-r", "--quiet", "--"]
+    };
+    if files.is_empty() {
+        debug("no files were specified, unstaging all", verbose);
+        args.push(".");
+    } else {
+        debug(&format!("files {:#?} were specified", files), verbose);
+        args.extend(files.iter().map(String::as_str));
+    }
+
+    if *dryrun {
+        debug("dry run was specified, not unstaging", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    let o = runcommand(repopath, &args, Stage::Unstage)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+/// discards unstaged changes to `files` (or everything) after listing them and
+/// asking for confirmation
+pub fn restore(repopath: &Path, files: &[String], dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    let mut diffargs = vec!["diff", "--name-only", "--"];
+    let mut args = vec!["restore", "--"];
+    if files.is_empty() {
+        debug("no files were specified, restoring all", verbose);
+        args.push(".");
+    } else {
+        debug(&format!("files {:#?} were specified", files), verbose);
+        diffargs.extend(files.iter().map(String::as_str));
+        args.extend(files.iter().map(String::as_str));
+    }
+
+    let changed = gitoutput(repopath, &diffargs).map_err(|e| e.withstage(Stage::Restore))?;
+    let changed: Vec<&str> = changed.lines().collect();
+    if changed.is_empty() {
+        info("    no unstaged changes to restore");
+        return Ok(());
+    }
+
+    error("    changes to these files will be lost:");
+    for file in &changed {
+        detail(&format!("      {}", file));
+    }
+
+    if *dryrun {
+        debug("dry run was specified, not restoring", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    let confirmed = Confirm::new()
+        .with_prompt(format!("discard changes to {} file(s)?", changed.len()))
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if !confirmed {
+        return Err(MeowError::pipeline(
+            Stage::Restore,
+            Cause::RestoreNotConfirmed,
+            "not restoring: discarding the changes was not confirmed",
+        ));
+    }
+
+    let o = runcommand(repopath, &args, Stage::Restore)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+fn hookexists(hooksdir: &Path, hook: &str) -> bool {
+    let path = hooksdir.join(hook);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+// the hook that stopped the commit, if one did. git prints nothing of its own
+// when a hook fails, so everything in the output is the hook's
+fn rejectinghook(repopath: &Path, started: SystemTime) -> Option<&'static str> {
+    // honours core.hooksPath
+    let hooksdir = gitoutput(repopath, &["rev-parse", "--git-path", "hooks"]).ok()?;
+    let hooksdir = repopath.join(hooksdir.trim());
+
+    // git only writes COMMIT_EDITMSG once pre-commit has passed
+    let editmsgwritten = gitoutput(repopath, &["rev-parse", "--git-path", "COMMIT_EDITMSG"])
+        .ok()
+        .and_then(|p| repopath.join(p.trim()).metadata().ok())
+        .and_then(|m| m.modified().ok())
+        .is_some_and(|modified| modified >= started);
+
+    if !editmsgwritten && hookexists(&hooksdir, "pre-commit") {
+        Some("pre-commit")
+    } else if hookexists(&hooksdir, "commit-msg") {
+        Some("commit-msg")
+    } else if hookexists(&hooksdir, "prepare-commit-msg") {
+        Some("prepare-commit-msg")
+    } else {
+        None
+    }
+}
+
+fn classifycommitfailure(repopath: &Path, output: &str, started: SystemTime) -> Cause {
+    if output.contains("nothing to commit")
+        || output.contains("nothing added to commit")
+        || output.contains("no changes added to commit")
+    {
+        return Cause::NothingToCommit;
+    }
+    if output.contains("unable to auto-detect email address") {
+        return Cause::IdentityNotConfigured;
+    }
+    // failures git reports itself, before or after the hooks run. anything
+    // else is left to the hooks, whose output could say anything
+    let cause = Cause::classify(output);
+    if matches!(
+        cause,
+        Cause::LockHeld
+            | Cause::MergeInProgress
+            | Cause::IdentityNotConfigured
+            | Cause::SigningFailed
+    ) {
+        return cause;
+    }
+
+    match rejectinghook(repopath, started) {
+        Some(hook) => Cause::HookRejected(hook.to_string()),
+        None => cause,
+    }
+}
+
+pub fn commit(repopath: &Path, message: &str, dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    let args = &["commit", "-m", message];
+
+    if *dryrun {
+        debug("dry run was specified, not committing", verbose);
+        printcommand(args);
+        return Ok(());
+    }
+
+    let started = SystemTime::now();
+    let o = executecommand(repopath, args, Stage::Commit)?;
+    if o.status.success() {
+        printcommitoutput(o, verbose);
+        return Ok(());
+    }
+
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&o.stdout),
+        String::from_utf8_lossy(&o.stderr)
+    );
+    let cause = classifycommitfailure(repopath, &output, started);
+
+    if matches!(cause, Cause::HookRejected(_) | Cause::Unknown) {
+        if let Cause::HookRejected(hook) = &cause {
+            error(&format!("    the {} hook rejected the commit:", hook));
+        }
+        for line in output.trim().lines() {
+            info(&format!("      {}", line));
+        }
+    }
+
+    Err(commanderror(args, &o, Stage::Commit).withcause(cause))
+}
+
+pub fn tagexists(repopath: &Path, name: &str) -> bool {
+    let tagref = format!("refs/tags/{}", name);
+    gitoutput(repopath, &["rev-parse", "-q", "--verify", &tagref]).is_ok()
+}
+
+pub fn tag(
+    repopath: &Path,
+    name: &str,
+    message: &str,
+    sign: &bool,
+    force: &bool,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    debug("checking if tag already exists", verbose);
+    if tagexists(repopath, name) {
+        if !*force {
+            return Err(MeowError::pipeline(
+                Stage::Tag,
+                Cause::TagExists,
+                format!("tag `{}` already exists", name),
+            ));
+        }
+        debug(
+            "tag exists but force-tag was specified, overwriting",
+            verbose,
+        );
+    }
+
+    let mut args = vec!["tag", if *sign { "-s" } else { "-a" }, name, "-m", message];
+    if *force {
+        args.push("--force");
+    }
+
+    if *dryrun {
+        debug("dry run was specified, not tagging", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    let o = runcommand(repopath, &args, Stage::Tag)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn push(
+    repopath: &Path,
+    upstream: Option<&str>,
+    tag: Option<&str>,
+    dryrun: &bool,
+    force: &u8,
+    ifincludes: &bool,
+    forcetag: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    let mut args = vec!["push"];
+    if let Some(upstreamval) = upstream {
+        debug(&format!("upstream {} was specified", upstreamval), verbose);
+        args.extend(["--set-upstream", "origin", upstreamval]);
+    }
+    args.extend(forceargs(repopath, *force, *ifincludes, verbose));
+
+    let tagref = tag.map(|t| format!("refs/tags/{}", t));
+    let mut tagargs = vec!["push", "origin"];
+    if let Some(tagref) = tagref.as_deref() {
+        debug(&format!("tag {} will be pushed", tagref), verbose);
+        tagargs.push(tagref);
+        if *forcetag {
+            debug("force-tag was specified, force pushing tag", verbose);
+            tagargs.push("--force");
+        }
+    }
+
+    if *dryrun {
+        debug("dry run was specified, not pushing", verbose);
+        printcommand(&args);
+        if tag.is_some() {
+            printcommand(&tagargs);
+        }
+        return Ok(());
+    }
+
+    debug("dry run was not specified, pushing", verbose);
+    let o = runcommand(repopath, &args, Stage::Push)?;
+    printpushoutput(o, None, verbose);
+
+    if tag.is_none() {
+        return Ok(());
+    }
+
+    debug("pushing tag", verbose);
+    match runcommand(repopath, &tagargs, Stage::Push) {
+        Ok(o) => {
+            printpushoutput(o, tag, verbose);
+            Ok(())
+        }
+        // the remote refuses to move an existing tag without --force
+        Err(e) if matches!(e.cause(), Cause::Rejected) => Err(e.withcause(Cause::TagExists)),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn addremote(
+    repopath: &Path,
+    remotename: &str,
+    remoteurl: &str,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    let args = vec!["remote", "add", remotename, remoteurl];
+
+    if *dryrun {
+        debug("dry run was specified, not adding remote", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    debug("dry run was not specified, adding remote", verbose);
+    let o = runcommand(repopath, &args, Stage::Remote)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+pub fn removeremote(
+    repopath: &Path,
+    remotename: &str,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<()> {
+    let args = vec!["remote", "remove", remotename];
+
+    if *dryrun {
+        debug("dry run was specified, not removing remote", verbose);
+        printcommand(&args);
+        return Ok(());
+    }
+
+    debug("dry run was not specified, removing remote", verbose);
+    let o = runcommand(repopath, &args, Stage::Remote)?;
+    printcommandoutput(o);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(repo)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    // a repository with one file staged and `hook` installed as pre-commit
+    fn repowithhook(name: &str, hook: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("meow-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["config", "user.name", "meow"]);
+        git(&root, &["config", "user.email", "meow@example.com"]);
+        git(&root, &["config", "core.hooksPath", ".git/hooks"]);
+        git(&root, &["config", "commit.gpgsign", "false"]);
+        fs::write(root.join("file"), "text").unwrap();
+        git(&root, &["add", "file"]);
+
+        let hookpath = root.join(".git").join("hooks").join("pre-commit");
+        fs::create_dir_all(hookpath.parent().unwrap()).unwrap();
+        fs::write(&hookpath, hook).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hookpath, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn hookoutputisnotgitserror() {
+        let root = repowithhook(
+            "hooknetwork",
+            "#!/bin/sh\necho 'fetching rules: Connection refused' >&2\nexit 1\n",
+        );
+        let result = commit(&root, "msg", &false, &0);
+        fs::remove_dir_all(&root).unwrap();
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.cause(),
+            &Cause::HookRejected(String::from("pre-commit"))
+        );
+        assert_eq!(
+            err.exitcode(),
+            Cause::HookRejected(String::new()).exitcode()
+        );
+    }
+
+    #[test]
+    fn passinghookdoesnotblame() {
+        let root = repowithhook("hookpasses", "#!/bin/sh\nexit 0\n");
+        let result = commit(&root, "msg", &false, &0);
+        fs::remove_dir_all(&root).unwrap();
+        assert!(result.is_ok());
+    }
+}
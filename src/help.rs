@@ -0,0 +1,387 @@
+use crate::args::Args;
+use clap::{Arg, ArgAction, Command, CommandFactory};
+use console::style;
+use meow::loggers::*;
+use std::collections::BTreeMap;
+
+// the section each command is listed under, in order
+const COMMANDSECTIONS: &[(&str, &[&str])] = &[
+    (
+        "commands",
+        &["stage", "commit", "push", "status", "unstage", "restore"],
+    ),
+    ("remotes", &["remote"]),
+    ("releases", &["release", "changelog"]),
+    ("setup", &["completions", "man"]),
+];
+
+// the order option sections are printed in. options without a heading go
+// under "options"
+const OPTIONSECTIONS: &[&str] = &[
+    "arguments",
+    "staging",
+    "pipeline",
+    "pushing",
+    "repositories",
+    "options",
+    "output",
+];
+
+// (command, example, what it does), shown by --help
+const EXAMPLES: &[(&str, &str, &str)] = &[
+    ("", "meow \"fix typo\"", "stage everything, commit and push"),
+    ("", "meow -a src/ \"msg\"", "stage only src/"),
+    ("", "meow -i \"msg\"", "pick which hunks to commit"),
+    (
+        "",
+        "meow -t v1.2.0 \"msg\"",
+        "tag the commit and push the tag",
+    ),
+    (
+        "",
+        "meow --all-repos \"msg\"",
+        "do it in every repo below here",
+    ),
+    (
+        "stage",
+        "meow stage src/ -i",
+        "pick hunks from src/ to stage",
+    ),
+    ("commit", "meow commit \"msg\"", "commit what's staged"),
+    (
+        "commit",
+        "meow commit -a Cargo.lock \"bump deps\"",
+        "stage Cargo.lock, then commit",
+    ),
+    (
+        "push",
+        "meow push -u feature",
+        "push and track origin/feature",
+    ),
+    (
+        "push",
+        "meow push -f --if-includes",
+        "force push with lease and if-includes",
+    ),
+    (
+        "status",
+        "meow status --all-repos",
+        "status of every repo below here",
+    ),
+    (
+        "unstage",
+        "meow unstage src/main.rs",
+        "take src/main.rs out of the index",
+    ),
+    ("restore", "meow restore", "discard every unstaged change"),
+    (
+        "remote add",
+        "meow remote add origin git@github.com:me/repo.git",
+        "add a remote",
+    ),
+    (
+        "remote remove",
+        "meow remote remove upstream",
+        "remove a remote",
+    ),
+    (
+        "release",
+        "meow release",
+        "pick the bump from commit messages",
+    ),
+    (
+        "release",
+        "meow release minor --changelog",
+        "bump the minor version and update CHANGELOG.md",
+    ),
+    (
+        "changelog",
+        "meow changelog --from v1.0.0",
+        "changes since v1.0.0",
+    ),
+    (
+        "completions",
+        "meow completions fish > ~/.config/fish/completions/meow.fish",
+        "install fish completions",
+    ),
+    (
+        "man",
+        "meow man ~/.local/share/man/man1",
+        "install the man pages",
+    ),
+];
+
+struct Row {
+    left: String,
+    help: String,
+    /// shown on its own lines under the row with --help
+    details: Vec<String>,
+}
+
+fn printsection(title: &str, rows: &[Row]) {
+    if rows.is_empty() {
+        return;
+    }
+    let width = rows.iter().map(|r| r.left.len()).max().unwrap_or_default();
+
+    important(&format!("\n{}:", title));
+    for row in rows {
+        let padding = " ".repeat(width - row.left.len());
+        info(&format!(
+            "  {}{}  {}",
+            style(&row.left).bold(),
+            padding,
+            row.help
+        ));
+        for line in &row.details {
+            info(&format!("  {}  {}", " ".repeat(width), style(line).dim()));
+        }
+    }
+}
+
+// `<name>`, `[name]` or `<name>...`, as the argument takes its values
+fn valuename(arg: &Arg) -> String {
+    let name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map_or_else(|| arg.get_id().to_string(), |n| n.to_string())
+        .to_lowercase();
+    let range = arg.get_num_args().unwrap_or_default();
+    let many = range.max_values() > 1 || matches!(arg.get_action(), ArgAction::Append);
+
+    let mut value = if range.min_values() == 0 || (arg.is_positional() && !arg.is_required_set()) {
+        format!("[{}]", name)
+    } else {
+        format!("<{}>", name)
+    };
+    if many {
+        value.push_str("...");
+    }
+    value
+}
+
+fn argleft(arg: &Arg) -> String {
+    if arg.is_positional() {
+        return valuename(arg);
+    }
+
+    let mut left = match arg.get_short() {
+        Some(short) => format!("-{}", short),
+        None => String::from("  "),
+    };
+    if let Some(long) = arg.get_long() {
+        left.push_str(if arg.get_short().is_some() {
+            ", "
+        } else {
+            "  "
+        });
+        left.push_str(&format!("--{}", long));
+    }
+    if arg.get_action().takes_values() {
+        left.push(' ');
+        left.push_str(&valuename(arg));
+    }
+    if matches!(arg.get_action(), ArgAction::Count) {
+        left.push_str("...");
+    }
+    left
+}
+
+fn argrow(arg: &Arg, long: bool) -> Row {
+    let help = if long {
+        arg.get_long_help().or(arg.get_help())
+    } else {
+        arg.get_help()
+    };
+    let mut details = Vec::new();
+
+    if long && arg.get_action().takes_values() {
+        let possible: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        if !possible.is_empty() && !arg.is_hide_possible_values_set() {
+            details.push(format!("one of: {}", possible.join(", ")));
+        }
+        let defaults: Vec<String> = arg
+            .get_default_values()
+            .iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect();
+        if !defaults.is_empty() && !arg.is_hide_default_value_set() {
+            details.push(format!("default: {}", defaults.join(", ")));
+        }
+    }
+
+    Row {
+        left: argleft(arg),
+        help: help.map(|h| h.to_string()).unwrap_or_default(),
+        details,
+    }
+}
+
+fn usagelines(cmd: &Command, fullname: &str) -> Vec<String> {
+    if let Some(usage) = cmd.get_overridden_usage() {
+        return usage
+            .to_string()
+            .lines()
+            .map(|l| l.trim().to_string())
+            .collect();
+    }
+
+    let mut usage = format!("{} [options]", fullname);
+    for arg in cmd.get_positionals().filter(|a| !a.is_hide_set()) {
+        usage.push(' ');
+        usage.push_str(&valuename(arg));
+    }
+    if cmd.has_subcommands() {
+        usage.push_str(" <command>");
+    }
+    vec![usage]
+}
+
+// a command and its nested commands, e.g. `remote add <name> <url>`
+fn commandrows(cmd: &Command, prefix: &str) -> Vec<Row> {
+    let name = format!("{}{}", prefix, cmd.get_name());
+    if cmd.has_subcommands() {
+        return cmd
+            .get_subcommands()
+            .filter(|c| !c.is_hide_set())
+            .flat_map(|c| commandrows(c, &format!("{} ", name)))
+            .collect();
+    }
+
+    let mut left = name;
+    for arg in cmd.get_positionals().filter(|a| !a.is_hide_set()) {
+        left.push(' ');
+        left.push_str(&valuename(arg));
+    }
+    vec![Row {
+        left,
+        help: cmd.get_about().map(|a| a.to_string()).unwrap_or_default(),
+        details: Vec::new(),
+    }]
+}
+
+fn printcommands(cmd: &Command) {
+    let mut listed: Vec<&str> = Vec::new();
+    let mut sections: Vec<(&str, Vec<Row>)> = Vec::new();
+    for (title, names) in COMMANDSECTIONS {
+        let rows = names
+            .iter()
+            .filter_map(|name| cmd.find_subcommand(name))
+            .flat_map(|c| commandrows(c, ""))
+            .collect();
+        listed.extend(names.iter());
+        sections.push((title, rows));
+    }
+
+    // commands nobody has slotted into a section yet
+    let rest: Vec<Row> = cmd
+        .get_subcommands()
+        .filter(|c| !c.is_hide_set() && !listed.contains(&c.get_name()))
+        .flat_map(|c| commandrows(c, ""))
+        .collect();
+    if let Some((_, rows)) = sections.first_mut() {
+        rows.extend(rest);
+    }
+
+    for (title, rows) in &sections {
+        printsection(title, rows);
+    }
+}
+
+fn printoptions(cmd: &Command, long: bool) {
+    let mut sections: BTreeMap<&str, Vec<Row>> = BTreeMap::new();
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        let title = if arg.is_positional() {
+            "arguments"
+        } else {
+            arg.get_help_heading().unwrap_or("options")
+        };
+        sections.entry(title).or_default().push(argrow(arg, long));
+    }
+
+    for title in OPTIONSECTIONS {
+        if let Some(rows) = sections.remove(title) {
+            printsection(title, &rows);
+        }
+    }
+    // headings missing from OPTIONSECTIONS still get printed, last
+    for (title, rows) in sections {
+        printsection(title, &rows);
+    }
+}
+
+/// prints help for the command at `path` (empty for meow itself) from the
+/// argument definitions. `long` (--help) adds details and examples
+pub fn printhelp(path: &[String], long: bool, aliases: &BTreeMap<String, String>) {
+    let mut root = Args::command();
+    // propagates global options into the subcommands
+    root.build();
+
+    let mut cmd = &root;
+    let mut names = vec![root.get_name().to_string()];
+    for name in path {
+        let Some(sub) = cmd.find_subcommand(name) else {
+            break;
+        };
+        cmd = sub;
+        names.push(sub.get_name().to_string());
+    }
+    let fullname = names.join(" ");
+
+    let about = if long {
+        cmd.get_long_about().or(cmd.get_about())
+    } else {
+        cmd.get_about()
+    };
+    if let Some(about) = about {
+        info(&about.to_string());
+    }
+
+    important("\nusage:");
+    for line in usagelines(cmd, &fullname) {
+        info(&format!("  {}", line));
+    }
+
+    printcommands(cmd);
+    printoptions(cmd, long);
+
+    if path.is_empty() && !aliases.is_empty() {
+        let rows: Vec<Row> = aliases
+            .iter()
+            .map(|(name, expansion)| Row {
+                left: name.clone(),
+                help: expansion.clone(),
+                details: Vec::new(),
+            })
+            .collect();
+        printsection("aliases", &rows);
+    }
+
+    let commandpath = path.join(" ");
+    let examples: Vec<Row> = EXAMPLES
+        .iter()
+        .filter(|(command, _, _)| *command == commandpath)
+        .map(|(_, example, description)| Row {
+            left: example.to_string(),
+            help: description.to_string(),
+            details: Vec::new(),
+        })
+        .collect();
+    if long {
+        printsection("examples", &examples);
+    } else if !examples.is_empty() || cmd.get_arguments().any(|a| a.get_long_help().is_some()) {
+        info(&format!(
+            "\n{}",
+            style(format!(
+                "use `{} --help` for details and examples",
+                fullname
+            ))
+            .dim()
+        ));
+    }
+}
@@ -0,0 +1,515 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::{gitoutput, runcommand},
+    loggers::*,
+    stages::shell,
+};
+use console::style;
+use dialoguer::Select;
+use std::{fs, path::Path};
+
+pub struct Hunk {
+    /// the `@@ -a,b +c,d @@` line
+    pub header: String,
+    pub lines: Vec<String>,
+    /// one flag per block of consecutive +/- lines
+    pub selected: Vec<bool>,
+}
+
+impl Hunk {
+    fn new(header: String, lines: Vec<String>) -> Hunk {
+        let blocks = countblocks(&lines);
+        Hunk {
+            header,
+            lines,
+            selected: vec![false; blocks],
+        }
+    }
+
+    fn blocks(&self) -> usize {
+        self.selected.len()
+    }
+
+    fn isselected(&self) -> bool {
+        self.selected.iter().any(|s| *s)
+    }
+
+    /// the hunk with unselected blocks neutralised: their removals become
+    /// context and their additions are dropped. counts are fixed by --recount
+    fn render(&self) -> String {
+        let mut out = format!("{}\n", self.header);
+        // whether the line before was kept, which decides a `\` marker's fate
+        let mut kept = true;
+        for (line, block) in self.lines.iter().zip(blockof(&self.lines)) {
+            if line.starts_with('\\') {
+                if kept {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                continue;
+            }
+
+            let selected = block.is_none_or(|b| self.selected.get(b).copied().unwrap_or(false));
+            kept = true;
+            match (selected, line.chars().next()) {
+                (true, _) => out.push_str(line),
+                (false, Some('-')) => {
+                    out.push(' ');
+                    out.push_str(&line[1..]);
+                }
+                (false, _) => {
+                    kept = false;
+                    continue;
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+pub struct FilePatch {
+    pub path: String,
+    /// `diff --git`, `index`, `---` and `+++` lines
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+    pub binary: bool,
+    /// the whole file is removed, so its hunks can't be staged in parts
+    pub deleted: bool,
+}
+
+fn ischange(line: &str) -> bool {
+    line.starts_with('+') || line.starts_with('-')
+}
+
+// the block each line belongs to, None for context. consecutive +/- lines
+// form a block, and a `\ No newline at end of file` marker belongs to the
+// line before it
+fn blockof(lines: &[String]) -> Vec<Option<usize>> {
+    let mut count = 0;
+    let mut previous = None;
+    lines
+        .iter()
+        .map(|line| {
+            let block = if line.starts_with('\\') {
+                previous
+            } else if ischange(line) {
+                Some(previous.unwrap_or_else(|| {
+                    count += 1;
+                    count - 1
+                }))
+            } else {
+                None
+            };
+            previous = block;
+            block
+        })
+        .collect()
+}
+
+fn countblocks(lines: &[String]) -> usize {
+    blockof(lines)
+        .into_iter()
+        .flatten()
+        .max()
+        .map_or(0, |b| b + 1)
+}
+
+/// unstaged changes to tracked files, split into hunks
+pub fn parsediff(diff: &str) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut hunk: Option<(String, Vec<String>)> = None;
+
+    let flush = |files: &mut Vec<FilePatch>, hunk: &mut Option<(String, Vec<String>)>| {
+        if let Some((header, lines)) = hunk.take()
+            && let Some(file) = files.last_mut()
+        {
+            file.hunks.push(Hunk::new(header, lines));
+        }
+    };
+
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            flush(&mut files, &mut hunk);
+            // a/<path> b/<path>
+            let path = paths
+                .split_once(" b/")
+                .map_or(paths, |(_, b)| b)
+                .to_string();
+            files.push(FilePatch {
+                path,
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+                binary: false,
+                deleted: false,
+            });
+        } else if line.starts_with("@@") {
+            flush(&mut files, &mut hunk);
+            hunk = Some((line.to_string(), Vec::new()));
+        } else if let Some((_, lines)) = hunk.as_mut() {
+            lines.push(line.to_string());
+        } else if let Some(file) = files.last_mut() {
+            if line.starts_with("Binary files") {
+                file.binary = true;
+            }
+            if line.starts_with("deleted file mode") {
+                file.deleted = true;
+            }
+            file.header.push(line.to_string());
+        }
+    }
+    flush(&mut files, &mut hunk);
+    files
+}
+
+fn printhunk(hunk: &Hunk, highlight: Option<usize>) {
+    emit(Level::Plain, &format!("{}", style(&hunk.header).cyan()));
+    for (line, block) in hunk.lines.iter().zip(blockof(&hunk.lines)) {
+        let dim = block.is_some_and(|b| highlight.is_some_and(|h| h != b));
+        let styled = match line.chars().next() {
+            Some('+') => style(line).green(),
+            Some('-') => style(line).red(),
+            _ => style(line).dim(),
+        };
+        emit(
+            Level::Plain,
+            &format!("{}", if dim { styled.dim() } else { styled }),
+        );
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Choice {
+    Stage,
+    Skip,
+    Split,
+    Edit,
+    SkipFile,
+    Quit,
+}
+
+// what can be done with a hunk. a deleted file's hunk is all of the file, so
+// splitting or editing it would leave a deletion with lines still in the file
+fn hunkchoices(deleted: bool, hunk: &Hunk) -> Vec<(Choice, &'static str)> {
+    let mut choices = vec![
+        (Choice::Stage, "stage this hunk"),
+        (Choice::Skip, "skip this hunk"),
+    ];
+    if !deleted && hunk.blocks() > 1 {
+        choices.push((Choice::Split, "split into smaller hunks"));
+    }
+    if !deleted {
+        choices.push((Choice::Edit, "edit this hunk"));
+    }
+    choices.extend([
+        (Choice::SkipFile, "skip the rest of this file"),
+        (Choice::Quit, "stop and stage what's selected"),
+    ]);
+    choices
+}
+
+fn partchoices() -> Vec<(Choice, &'static str)> {
+    vec![
+        (Choice::Stage, "stage this hunk"),
+        (Choice::Skip, "skip this hunk"),
+        (Choice::Edit, "edit this hunk"),
+        (Choice::SkipFile, "skip the rest of this file"),
+        (Choice::Quit, "stop and stage what's selected"),
+    ]
+}
+
+fn choose(prompt: &str, mut choices: Vec<(Choice, &str)>) -> MeowResult<Choice> {
+    let labels: Vec<&str> = choices.iter().map(|(_, label)| *label).collect();
+    let index = Select::new()
+        .with_prompt(prompt)
+        .items(&labels)
+        .default(0)
+        .interact()
+        .map_err(|e| {
+            MeowError::pipeline(
+                Stage::Add,
+                Cause::Unknown,
+                format!("interactive staging needs a terminal: {}", e),
+            )
+        })?;
+    Ok(choices.swap_remove(index).0)
+}
+
+fn gitpath(repo: &Path, name: &str) -> MeowResult<String> {
+    let path = gitoutput(repo, &["rev-parse", "--git-path", name])?;
+    Ok(repo.join(path.trim()).to_string_lossy().into_owned())
+}
+
+/// opens the hunk in the user's editor and replaces its lines with the result
+fn edithunk(repo: &Path, hunk: &mut Hunk) -> MeowResult<()> {
+    let editerror = |message: String| MeowError::pipeline(Stage::Add, Cause::Unknown, message);
+
+    let path = gitpath(repo, "MEOW_HUNK_EDIT.diff")?;
+    let mut contents = String::from(
+        "# edit the hunk, then save and close the editor.\n\
+         # to drop a `-` line, make it a context line (` `). to drop a `+` line, delete it.\n\
+         # lines starting with # are removed.\n",
+    );
+    contents.push_str(&hunk.header);
+    contents.push('\n');
+    for line in &hunk.lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+    fs::write(&path, contents)
+        .map_err(|e| editerror(format!("could not write {}: {}", path, e)))?;
+
+    let editor = gitoutput(repo, &["var", "GIT_EDITOR"])?;
+    let status = shell(&format!("{} \"{}\"", editor.trim(), path))
+        .current_dir(repo)
+        .status()
+        .map_err(|e| editerror(format!("could not run the editor: {}", e)))?;
+    if !status.success() {
+        let _ = fs::remove_file(&path);
+        return Err(editerror(String::from("the editor exited unsuccessfully")));
+    }
+
+    let edited = fs::read_to_string(&path)
+        .map_err(|e| editerror(format!("could not read {}: {}", path, e)))?;
+    let _ = fs::remove_file(&path);
+
+    let lines: Vec<String> = edited
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with("@@"))
+        .map(String::from)
+        .collect();
+    *hunk = Hunk::new(hunk.header.clone(), lines);
+    hunk.selected.fill(true);
+    Ok(())
+}
+
+// asks about each hunk of each file, stopping early if the user quits
+fn walkhunks(repo: &Path, files: &mut [FilePatch]) -> MeowResult<()> {
+    let total: usize = files.iter().map(|f| f.hunks.len()).sum();
+    let mut current = 0;
+
+    for file in files.iter_mut() {
+        if file.binary {
+            info(&format!(
+                "    skipping binary file {}",
+                style(&file.path).bold()
+            ));
+            continue;
+        }
+
+        'hunks: for hunk in file.hunks.iter_mut() {
+            current += 1;
+            emit(
+                Level::Plain,
+                &format!("\n{}", style(&file.path).bold().magenta()),
+            );
+            printhunk(hunk, None);
+
+            let prompt = format!("hunk {}/{}", current, total);
+            match choose(&prompt, hunkchoices(file.deleted, hunk))? {
+                Choice::Stage => hunk.selected.fill(true),
+                Choice::Skip => (),
+                Choice::Edit => edithunk(repo, hunk)?,
+                Choice::SkipFile => break 'hunks,
+                Choice::Quit => return Ok(()),
+                Choice::Split => {
+                    for block in 0..hunk.blocks() {
+                        emit(Level::Plain, "");
+                        printhunk(hunk, Some(block));
+                        let prompt = format!(
+                            "hunk {}/{}, part {}/{}",
+                            current,
+                            total,
+                            block + 1,
+                            hunk.blocks()
+                        );
+                        match choose(&prompt, partchoices())? {
+                            Choice::Stage => hunk.selected[block] = true,
+                            Choice::Edit => {
+                                edithunk(repo, hunk)?;
+                                break;
+                            }
+                            Choice::SkipFile => break 'hunks,
+                            Choice::Quit => return Ok(()),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// builds a patch from the selected hunks, ready for `git apply --cached`
+pub fn buildpatch(files: &[FilePatch]) -> String {
+    let mut patch = String::new();
+    for file in files {
+        let hunks: Vec<&Hunk> = file.hunks.iter().filter(|h| h.isselected()).collect();
+        if hunks.is_empty() {
+            continue;
+        }
+        for line in &file.header {
+            patch.push_str(line);
+            patch.push('\n');
+        }
+        for hunk in hunks {
+            patch.push_str(&hunk.render());
+        }
+    }
+    patch
+}
+
+/// walks through the unstaged hunks of `paths` (or every tracked file) and
+/// stages the ones the user picks
+pub fn stagehunks(repo: &Path, paths: &[String], dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    let diff = gitoutput(repo, &args).map_err(|e| e.withstage(Stage::Add))?;
+
+    let mut files = parsediff(&diff);
+    if files.is_empty() {
+        info("    no unstaged changes to tracked files");
+        return Ok(());
+    }
+    debug(
+        &format!("{} file(s) with unstaged changes", files.len()),
+        verbose,
+    );
+
+    walkhunks(repo, &mut files)?;
+    let patch = buildpatch(&files);
+    if patch.is_empty() {
+        info("    no hunks selected");
+        return Ok(());
+    }
+
+    let patchpath = gitpath(repo, "MEOW_HUNKS.patch")?;
+    let applyargs = ["apply", "--cached", "--recount", patchpath.as_str()];
+    if *dryrun {
+        debug("dry run was specified, not staging hunks", verbose);
+        printcommand(&applyargs);
+        return Ok(());
+    }
+
+    fs::write(&patchpath, patch).map_err(|e| {
+        MeowError::pipeline(
+            Stage::Add,
+            Cause::Unknown,
+            format!("could not write {}: {}", patchpath, e),
+        )
+    })?;
+    let result = runcommand(repo, &applyargs, Stage::Add);
+    let _ = fs::remove_file(&patchpath);
+    result?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,4 @@
+-use a;
++use b;
+ fn main() {}
+-last
+\\ No newline at end of file
++last line
+\\ No newline at end of file
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+index 3333333..0000000
+--- a/old.txt
++++ /dev/null
+@@ -1,3 +0,0 @@
+-one
+-
+-three
+diff --git a/logo.png b/logo.png
+index 4444444..5555555 100644
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn parsefiles() {
+        let files = parsediff(DIFF);
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/lib.rs", "old.txt", "logo.png"]);
+
+        assert_eq!(files[0].header.len(), 4);
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].header, "@@ -1,4 +1,4 @@");
+        assert_eq!(files[0].hunks[0].lines.len(), 7);
+        assert!(!files[0].deleted && !files[0].binary);
+
+        assert!(files[1].deleted);
+        assert!(files[2].binary);
+        assert!(files[2].hunks.is_empty());
+    }
+
+    #[test]
+    fn newlinemarkerjoinsitsblock() {
+        let files = parsediff(DIFF);
+        // the markers don't split `-last` / `+last line` into more blocks
+        assert_eq!(files[0].hunks[0].blocks(), 2);
+        assert_eq!(files[1].hunks[0].blocks(), 1);
+    }
+
+    #[test]
+    fn renderpartial() {
+        let mut files = parsediff(DIFF);
+        let hunk = &mut files[0].hunks[0];
+
+        hunk.selected = vec![true, false];
+        assert_eq!(
+            hunk.render(),
+            "@@ -1,4 +1,4 @@\n-use a;\n+use b;\n fn main() {}\n last\n\
+             \\ No newline at end of file\n"
+        );
+
+        hunk.selected = vec![false, true];
+        assert_eq!(
+            hunk.render(),
+            "@@ -1,4 +1,4 @@\n use a;\n fn main() {}\n-last\n\
+             \\ No newline at end of file\n+last line\n\
+             \\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn patchofselectedhunks() {
+        let mut files = parsediff(DIFF);
+        assert_eq!(buildpatch(&files), "");
+
+        files[0].hunks[0].selected = vec![false, true];
+        let patch = buildpatch(&files);
+        assert!(patch.starts_with("diff --git a/src/lib.rs b/src/lib.rs\nindex"));
+        assert!(patch.contains("+++ b/src/lib.rs\n@@ -1,4 +1,4 @@\n use a;\n"));
+        assert!(!patch.contains("old.txt"));
+    }
+
+    #[test]
+    fn deletedfilesstagewhole() {
+        let files = parsediff(DIFF);
+        let choices: Vec<Choice> = hunkchoices(files[1].deleted, &files[1].hunks[0])
+            .into_iter()
+            .map(|(choice, _)| choice)
+            .collect();
+        assert_eq!(
+            choices,
+            [Choice::Stage, Choice::Skip, Choice::SkipFile, Choice::Quit]
+        );
+
+        let choices: Vec<Choice> = hunkchoices(files[0].deleted, &files[0].hunks[0])
+            .into_iter()
+            .map(|(choice, _)| choice)
+            .collect();
+        assert!(choices.contains(&Choice::Split) && choices.contains(&Choice::Edit));
+    }
+}
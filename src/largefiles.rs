@@ -0,0 +1,281 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::{gitoutput, runcommand},
+    loggers::*,
+};
+use console::style;
+use dialoguer::Select;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// what happens to staged files that are too large or are unattributed binaries
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeFilePolicy {
+    /// ask whether to unstage, track with lfs or keep each file
+    #[default]
+    Prompt,
+    /// refuse to continue
+    Block,
+    /// only report them
+    Warn,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// newly staged files larger than this are flagged, e.g. "10MB"
+    #[serde(rename = "max-size")]
+    pub maxsize: String,
+    #[serde(rename = "on-large")]
+    pub onlarge: LargeFilePolicy,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            maxsize: String::from("10MB"),
+            onlarge: LargeFilePolicy::Prompt,
+        }
+    }
+}
+
+pub struct FlaggedFile {
+    pub path: String,
+    pub size: u64,
+    pub toolarge: bool,
+    /// binary and not covered by lfs or a .gitattributes binary rule
+    pub unattributed: bool,
+}
+
+/// parses sizes like `500KB`, `10MB`, `1.5GB` or plain bytes
+pub fn parsesize(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_uppercase();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+pub fn formatsize(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1}GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1}MB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1}KB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{}B", b),
+    }
+}
+
+// the filter and diff attributes of each path, as `git check-attr` reports them
+fn attributed(reporoot: &Path, paths: &[&str]) -> Vec<String> {
+    if paths.is_empty() {
+        return Vec::new();
+    }
+
+    let mut args = vec!["check-attr", "filter", "diff", "--"];
+    args.extend(paths);
+    gitoutput(reporoot, &args)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| line.ends_with(": filter: lfs") || line.ends_with(": diff: unset"))
+        .filter_map(|line| line.split_once(": ").map(|(path, _)| path.to_string()))
+        .collect()
+}
+
+/// newly staged files over `maxsize` bytes or binaries without attributes
+pub fn flaggedfiles(reporoot: &Path, maxsize: u64) -> MeowResult<Vec<FlaggedFile>> {
+    // binary files show up as `-\t-\t<path>`
+    let numstat = gitoutput(
+        reporoot,
+        &[
+            "diff",
+            "--cached",
+            "--numstat",
+            "--diff-filter=A",
+            "--no-renames",
+        ],
+    )?;
+    let added: Vec<(bool, &str)> = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let insertions = parts.next()?;
+            parts.next()?;
+            Some((insertions == "-", parts.next()?))
+        })
+        .collect();
+
+    let binaries: Vec<&str> = added
+        .iter()
+        .filter(|(binary, _)| *binary)
+        .map(|(_, path)| *path)
+        .collect();
+    let covered = attributed(reporoot, &binaries);
+
+    Ok(added
+        .into_iter()
+        .filter_map(|(binary, path)| {
+            let size = fs::metadata(reporoot.join(path))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            let toolarge = size > maxsize;
+            let unattributed = binary && !covered.iter().any(|c| c == path);
+            (toolarge || unattributed).then(|| FlaggedFile {
+                path: path.to_string(),
+                size,
+                toolarge,
+                unattributed,
+            })
+        })
+        .collect())
+}
+
+fn unstage(reporoot: &Path, path: &str) -> MeowResult<()> {
+    runcommand(
+        reporoot,
+        &["rm", "--cached", "--quiet", "--", path],
+        Stage::Files,
+    )?;
+    Ok(())
+}
+
+fn trackwithlfs(reporoot: &Path, path: &str) -> MeowResult<()> {
+    if gitoutput(reporoot, &["lfs", "version"]).is_err() {
+        return Err(MeowError::pipeline(
+            Stage::Files,
+            Cause::LargeFile,
+            "git lfs is not installed",
+        ));
+    }
+
+    runcommand(
+        reporoot,
+        &["lfs", "track", "--filename", path],
+        Stage::Files,
+    )?;
+    // restage so the file goes through the lfs filter
+    unstage(reporoot, path)?;
+    runcommand(
+        reporoot,
+        &["add", "--", path, ".gitattributes"],
+        Stage::Files,
+    )?;
+    Ok(())
+}
+
+/// checks newly staged files against the size limit and for unattributed
+/// binaries, then warns, blocks or asks what to do with each one
+pub fn checkstagedfiles(reporoot: &Path, config: &FilesConfig, verbose: &u8) -> MeowResult<()> {
+    debug("checking staged files for large or binary files", verbose);
+    let maxsize = parsesize(&config.maxsize).ok_or_else(|| {
+        MeowError::pipeline(
+            Stage::Setup,
+            Cause::InvalidConfig,
+            format!("invalid files.max-size `{}`", config.maxsize),
+        )
+    })?;
+
+    let flagged = flaggedfiles(reporoot, maxsize)?;
+    if flagged.is_empty() {
+        debug("no large or binary files staged", verbose);
+        return Ok(());
+    }
+
+    info("    large or binary files staged:");
+    for file in &flagged {
+        let mut reasons = Vec::new();
+        if file.toolarge {
+            reasons.push(format!("over {}", config.maxsize));
+        }
+        if file.unattributed {
+            reasons.push(String::from("binary not tracked by lfs"));
+        }
+        info(&format!(
+            "      {} ({}, {})",
+            style(&file.path).bold(),
+            formatsize(file.size),
+            reasons.join(", ")
+        ));
+    }
+
+    let refuse = |count: usize| {
+        MeowError::pipeline(
+            Stage::Files,
+            Cause::LargeFile,
+            format!("not continuing: {} large or binary file(s) staged", count),
+        )
+    };
+
+    match config.onlarge {
+        LargeFilePolicy::Warn => Ok(()),
+        LargeFilePolicy::Block => Err(refuse(flagged.len())),
+        LargeFilePolicy::Prompt => {
+            let choices = ["unstage it", "track it with git lfs", "keep it"];
+            for file in &flagged {
+                let choice = Select::new()
+                    .with_prompt(format!("what should happen to {}?", file.path))
+                    .items(choices)
+                    .default(0)
+                    .interact();
+                match choice {
+                    Ok(0) => unstage(reporoot, &file.path)?,
+                    Ok(1) => trackwithlfs(reporoot, &file.path)?,
+                    Ok(_) => debug(&format!("keeping {}", file.path), verbose),
+                    // no terminal to ask on
+                    Err(_) => return Err(refuse(flagged.len())),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsebytes() {
+        assert_eq!(parsesize("0"), Some(0));
+        assert_eq!(parsesize("512"), Some(512));
+        assert_eq!(parsesize("512B"), Some(512));
+    }
+
+    #[test]
+    fn parseunits() {
+        assert_eq!(parsesize("500KB"), Some(500 << 10));
+        assert_eq!(parsesize("10MB"), Some(10 << 20));
+        assert_eq!(parsesize("2GB"), Some(2 << 30));
+        assert_eq!(parsesize("3M"), Some(3 << 20));
+    }
+
+    #[test]
+    fn parselenient() {
+        assert_eq!(parsesize(" 10 mb "), Some(10 << 20));
+        assert_eq!(parsesize("1kb"), Some(1 << 10));
+    }
+
+    #[test]
+    fn parsefractions() {
+        assert_eq!(parsesize("1.5GB"), Some(3 << 29));
+        assert_eq!(parsesize("0.5KB"), Some(512));
+    }
+
+    #[test]
+    fn parseinvalid() {
+        assert_eq!(parsesize(""), None);
+        assert_eq!(parsesize("MB"), None);
+        assert_eq!(parsesize("10TB"), None);
+        assert_eq!(parsesize("-1MB"), None);
+        assert_eq!(parsesize("1.2.3MB"), None);
+    }
+}
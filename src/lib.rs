@@ -0,0 +1,32 @@
+//! meow is a small git wrapper that stages, commits and pushes in one go.
+//!
+//! the `meow` binary is a thin consumer of this library. the same pipeline can
+//! be driven from other tools with [`Pipeline`], with output routed through an
+//! [`OutputSink`](loggers::OutputSink).
+
+pub mod aliases;
+pub mod artifacts;
+pub mod branches;
+pub mod changelog;
+pub mod config;
+pub mod error;
+pub mod forcepush;
+pub mod git;
+pub mod hunks;
+pub mod largefiles;
+pub mod logfile;
+pub mod loggers;
+pub mod multirepo;
+pub mod pipeline;
+pub mod release;
+pub mod secrets;
+pub mod stages;
+pub mod submodules;
+pub mod theme;
+pub mod tracking;
+pub mod trust;
+
+pub use error::{Cause, MeowError, MeowResult, Stage};
+pub use git::{getcleanroot, getrootdir};
+pub use pipeline::{Outcome, Pipeline, PipelineReport, StepReport};
+pub use stages::{CustomStage, PipelineStage};
@@ -0,0 +1,176 @@
+use crate::{
+    error::{Cause, MeowError, MeowResult, Stage},
+    largefiles::parsesize,
+    loggers::Level,
+};
+use chrono::Local;
+use homedir::my_home;
+use serde::Deserialize;
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    /// write every run to the log file, not just those given --log
+    pub enabled: bool,
+    /// the log is rotated once it grows past this, e.g. "1MB"
+    #[serde(rename = "max-size")]
+    pub maxsize: String,
+    /// how many rotated logs are kept next to the current one
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            maxsize: String::from("1MB"),
+            keep: 3,
+        }
+    }
+}
+
+struct LogFile {
+    file: File,
+    path: PathBuf,
+    runid: String,
+}
+
+static LOG: Mutex<Option<LogFile>> = Mutex::new(None);
+
+fn logerror(message: String) -> MeowError {
+    MeowError::pipeline(Stage::Setup, Cause::Unknown, message)
+}
+
+/// $XDG_STATE_HOME/meow, or ~/.local/state/meow
+pub fn statedir() -> Option<PathBuf> {
+    let state = match env::var_os("XDG_STATE_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => my_home().ok()??.join(".local").join("state"),
+    };
+    Some(state.join("meow"))
+}
+
+// meow.log -> meow.log.1 -> ... -> meow.log.<keep>, dropping the oldest
+fn rotate(path: &Path, keep: usize) {
+    let numbered = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    if keep == 0 {
+        let _ = fs::remove_file(path);
+        return;
+    }
+
+    let _ = fs::remove_file(numbered(keep));
+    for n in (1..keep).rev() {
+        let _ = fs::rename(numbered(n), numbered(n + 1));
+    }
+    let _ = fs::rename(path, numbered(1));
+}
+
+// short enough to grep for, unique enough to tell overlapping runs apart
+fn newrunid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() ^ d.as_secs() as u32)
+        .unwrap_or_default();
+    format!("{:08x}", nanos ^ process::id().rotate_left(16))
+}
+
+/// starts logging this run to meow.log in the state directory, rotating the
+/// log first if it's too big. returns the log's path
+pub fn openlog(config: &LogConfig) -> MeowResult<PathBuf> {
+    let dir =
+        statedir().ok_or_else(|| logerror(String::from("could not find a home directory")))?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| logerror(format!("could not create {}: {}", dir.display(), e)))?;
+
+    let path = dir.join("meow.log");
+    let maxsize = parsesize(&config.maxsize).ok_or_else(|| {
+        MeowError::pipeline(
+            Stage::Setup,
+            Cause::InvalidConfig,
+            format!("invalid log max-size `{}`", config.maxsize),
+        )
+    })?;
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= maxsize) {
+        rotate(&path, config.keep);
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| logerror(format!("could not open {}: {}", path.display(), e)))?;
+
+    if let Ok(mut log) = LOG.lock() {
+        *log = Some(LogFile {
+            file,
+            path: path.clone(),
+            runid: newrunid(),
+        });
+    }
+
+    let cwd = env::current_dir().unwrap_or_default();
+    let argv: Vec<String> = env::args().collect();
+    writelog(
+        Level::Plain,
+        &format!(
+            "meow {} started in {}: {}",
+            env!("CARGO_PKG_VERSION"),
+            cwd.display(),
+            argv.join(" ")
+        ),
+    );
+    Ok(path)
+}
+
+/// the path of the log this run writes to, if logging is on
+pub fn logpath() -> Option<PathBuf> {
+    LOG.lock().ok()?.as_ref().map(|log| log.path.clone())
+}
+
+fn levelname(level: Level) -> &'static str {
+    match level {
+        Level::Important => "important",
+        Level::Info => "info",
+        Level::Success => "success",
+        Level::Error => "error",
+        Level::Hint => "hint",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+        Level::Command => "command",
+        Level::Plain => "plain",
+    }
+}
+
+/// appends `text` to the log, one timestamped line per line of text. does
+/// nothing unless `openlog` was called
+pub fn writelog(level: Level, text: &str) {
+    let Ok(mut log) = LOG.lock() else {
+        return;
+    };
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+
+    let timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+    let text = console::strip_ansi_codes(text);
+    let mut entry = String::new();
+    for line in text.trim_matches('\n').lines() {
+        entry.push_str(&format!(
+            "{} {} {:<9} {}\n",
+            timestamp,
+            log.runid,
+            levelname(level),
+            line
+        ));
+    }
+    let _ = log.file.write_all(entry.as_bytes());
+}
@@ -1,70 +1,188 @@
-use crate::args::Args;
-use clap::CommandFactory;
-use console::{Term, style};
-use std::{num::ParseIntError, process::Output};
-
-pub fn printhelp() {
-    let mut cmd = Args::command();
-    let helptext = cmd.render_help().to_string();
-    let mut usagetext = String::new();
-
-    for line in helptext.lines() {
-        if line.starts_with("Usage:") {
-            usagetext = String::from(line.strip_prefix("Usage:").unwrap_or(line));
-            important(&format!("usage: {}", usagetext));
-        } else if line.starts_with("Arguments:") {
-            important(&format!(
-                "arguments: {}",
-                line.strip_prefix("Arguments:").unwrap_or(line)
-            ));
-        } else if line.starts_with("Options:") {
-            println!(
-                "{}",
-                &format!(
-                    "{} {}",
-                    style("options:").cyan(),
-                    style(formatoptionsline(
-                        line.strip_prefix("Options:").unwrap_or(line).to_string()
-                    ))
-                )
-            );
-        } else {
-            info(line);
+use crate::{
+    error::MeowError,
+    logfile::{logpath, writelog},
+    theme::Theme,
+};
+use console::Term;
+use std::{
+    cell::RefCell,
+    num::ParseIntError,
+    process::Output,
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Important,
+    Info,
+    Success,
+    Error,
+    Hint,
+    Debug,
+    /// full command output, environment and timing, shown with -vv
+    Trace,
+    Command,
+    /// already styled text, printed as is. still shown with -q
+    Plain,
+}
+
+/// where meow's output goes. the cli prints to the terminal, library users can
+/// install their own sink with `setsink` to capture or redirect it
+pub trait OutputSink: Send + Sync {
+    fn write(&self, level: Level, text: &str);
+}
+
+pub struct TerminalSink;
+
+impl OutputSink for TerminalSink {
+    fn write(&self, level: Level, text: &str) {
+        if quiet() && !matches!(level, Level::Error | Level::Hint | Level::Plain) {
+            return;
+        }
+
+        let style = levelstyle(level);
+        match level {
+            Level::Error => {
+                let style = style.for_stderr();
+                let _ = Term::stderr().write_line(&format!("{}", style.apply_to(text)));
+            }
+            Level::Hint => {
+                let style = style.for_stderr();
+                let _ = Term::stderr().write_line(&format!(
+                    "    {} {}",
+                    style.clone().bold().apply_to("hint:"),
+                    style.apply_to(text)
+                ));
+            }
+            // diagnostics go to stderr so they don't mix with data on stdout
+            Level::Debug | Level::Trace => {
+                let label = if level == Level::Debug {
+                    "[DEBUG]"
+                } else {
+                    "[TRACE]"
+                };
+                let style = style.for_stderr();
+                let _ = Term::stderr().write_line(&format!("{} {}", label, style.apply_to(text)));
+            }
+            Level::Command => println!("  {}", style.apply_to(text)),
+            _ => println!("{}", style.apply_to(text)),
         }
     }
+}
 
-    important(&format!("\nusage: {}", usagetext));
+static THEME: RwLock<Option<Theme>> = RwLock::new(None);
+static ASCII: AtomicBool = AtomicBool::new(false);
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// sets the -v count and -q for output that isn't handed a verbosity, such as
+/// the trace of each git command
+pub fn setverbosity(verbose: u8, quiet: bool) {
+    VERBOSITY.store(verbose, Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
 }
 
-pub fn formatoptionsline(line: String) -> String {
-    let mut result = String::new();
-    let startidx: usize;
-    let endidx: usize;
-    if let Some(dashidx) = line.find('-') {
-        if let Some(commaidx) = line.find(",") {
-            startidx = dashidx + 1;
-            endidx = startidx + commaidx - 1;
-        } else {
-            startidx = dashidx + 1;
-            endidx = line.len();
-        }
+pub fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// whether only errors (and the context of prompts) are printed
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn settheme(theme: Theme) {
+    if let Ok(mut current) = THEME.write() {
+        *current = Some(theme);
+    }
+}
+
+fn levelstyle(level: Level) -> console::Style {
+    match THEME.read() {
+        Ok(theme) => match theme.as_ref() {
+            Some(theme) => theme.style(level),
+            None => Theme::default().style(level),
+        },
+        Err(_) => Theme::default().style(level),
+    }
+}
+
+/// `text` in the theme's style for `level`, for lines that mix levels
+pub fn paint(level: Level, text: &str) -> String {
+    levelstyle(level).apply_to(text).to_string()
+}
+
+pub fn setascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
+
+pub fn asciimode() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+/// `unicode`, or `ascii` in ascii mode
+pub fn symbol<'a>(unicode: &'a str, ascii: &'a str) -> &'a str {
+    if asciimode() { ascii } else { unicode }
+}
+
+/// like `symbol`, but also falls back where the terminal can't show emoji
+pub fn emoji<'a>(emoji: &'a str, ascii: &'a str) -> &'a str {
+    if Term::stdout().features().wants_emoji() {
+        symbol(emoji, ascii)
     } else {
-        startidx = 0;
-        endidx = 0;
+        ascii
+    }
+}
+
+static SINK: RwLock<Option<Box<dyn OutputSink>>> = RwLock::new(None);
+
+pub fn setsink(sink: Box<dyn OutputSink>) {
+    if let Ok(mut current) = SINK.write() {
+        *current = Some(sink);
     }
+}
 
-    result.push_str(&format!(
-        "{}{}",
-        style(line[startidx..endidx].trim().to_string()).bold(),
-        style(line[endidx..].trim().to_string())
-    ));
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<(Level, String)>>> = const { RefCell::new(None) };
+}
+
+/// runs `f` with this thread's output held back rather than printed, and
+/// returns it alongside the result. multi-repo mode uses this to keep repos
+/// running in parallel from interleaving their output
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<(Level, String)>) {
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let output = CAPTURED.with(|captured| captured.borrow_mut().take());
+    (result, output.unwrap_or_default())
+}
 
-    result
+pub fn emit(level: Level, text: &str) {
+    writelog(level, text);
+    let held = CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(output) => {
+            output.push((level, text.to_string()));
+            true
+        }
+        None => false,
+    });
+    if held {
+        return;
+    }
+
+    match SINK.read() {
+        Ok(sink) => match sink.as_ref() {
+            Some(sink) => sink.write(level, text),
+            None => TerminalSink.write(level, text),
+        },
+        Err(_) => TerminalSink.write(level, text),
+    }
 }
 
-pub fn printcommand(command: &Vec<&str>) {
-    let msg = format!("  {}", style(command.join(" ")).cyan());
-    println!("{}", msg);
+pub fn printcommand(command: &[&str]) {
+    emit(Level::Command, &command.join(" "));
 }
 
 pub fn printcommandoutput(output: Output) {
@@ -156,7 +274,7 @@ pub fn printcommitoutput(output: Output, verbose: &u8) {
 
     if let Some(fileschangedpart) = partsfileschanged.first() {
         let parts = fileschangedpart.split_whitespace().collect::<Vec<&str>>();
-        if parts.len() >= 1 {
+        if !parts.is_empty() {
             fileschangedcount = parts[0];
         }
     }
@@ -211,14 +329,14 @@ pub fn printcommitoutput(output: Output, verbose: &u8) {
             String::new()
         }
     ));
-    println!(
-        "{}",
-        format!(
+    emit(
+        Level::Info,
+        &format!(
             "    {}{}{}",
-            style(format!("{} insertions (+)", insertions)).green(),
-            style(", ").magenta(),
-            style(format!("{} deletions (-)", deletions)).red()
-        )
+            paint(Level::Success, &format!("{} insertions (+)", insertions)),
+            paint(Level::Info, ", "),
+            paint(Level::Error, &format!("{} deletions (-)", deletions))
+        ),
     );
 
     if let Some(modeline) = modeline {
@@ -234,7 +352,7 @@ pub fn printcommitoutput(output: Output, verbose: &u8) {
     }
 }
 
-pub fn printpushoutput(output: Output, verbose: &u8) {
+pub fn printpushoutput(output: Output, tag: Option<&str>, verbose: &u8) {
     debug("parsing push command output", verbose);
     let stderr = String::from_utf8_lossy(&output.stderr);
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -242,6 +360,7 @@ pub fn printpushoutput(output: Output, verbose: &u8) {
 
     let mut remoteline = None;
     let mut branchline = None;
+    let mut tagline = None;
     let mut upstreamline = None;
     let mut uptodate = false;
 
@@ -250,7 +369,13 @@ pub fn printpushoutput(output: Output, verbose: &u8) {
         if trimmed.starts_with("To ") {
             remoteline = Some(trimmed.to_string());
         } else if trimmed.contains("->") {
-            branchline = Some(trimmed.to_string());
+            let istag = trimmed.contains("[new tag]")
+                || tag.is_some_and(|t| trimmed.contains(&format!("{} -> {}", t, t)));
+            if istag {
+                tagline = Some(trimmed.to_string());
+            } else {
+                branchline = Some(trimmed.to_string());
+            }
         } else if trimmed.starts_with("Branch ") {
             upstreamline = Some(trimmed.to_string());
         } else if trimmed == "Everything up-to-date" {
@@ -259,59 +384,108 @@ pub fn printpushoutput(output: Output, verbose: &u8) {
     }
 
     if uptodate {
-        info("    Everything up-to-date");
+        match tag {
+            Some(t) => info(&format!("    tag {} is up-to-date", t)),
+            None => info("    Everything up-to-date"),
+        }
         return;
     }
 
-    if remoteline.is_none() && branchline.is_none() && upstreamline.is_none() {
+    if remoteline.is_none() && branchline.is_none() && tagline.is_none() && upstreamline.is_none() {
         debug("Could not parse push output, falling back", verbose);
         printcommandoutput(output);
         return;
     }
 
-    if let Some(line) = remoteline {
+    if tag.is_none()
+        && let Some(line) = remoteline
+    {
         info(&format!("    {}", line));
     }
     if let Some(line) = branchline {
         info(&format!("    {}", line));
     }
+    if let Some(line) = tagline {
+        info(&format!("    tag: {}", line));
+    }
     if let Some(line) = upstreamline {
         info(&format!("    {}", line));
     }
 }
 
-pub fn _fatalerror(error: &str) {
-    let term = Term::stderr();
-    term.write_line(&format!("{}", style("error: ").red()))
-        .unwrap();
-    term.write_line(&format!("  {}", style(error).red()))
-        .unwrap();
-    term.write_line(&format!(
-        "{}",
-        style("run `meow -h` for detailed help").red()
-    ))
-    .unwrap();
+pub fn _fatalerror(text: &str) {
+    error("error: ");
+    error(&format!("  {}", text));
+    error("run `meow -h` for detailed help");
+}
+
+pub fn printerror(err: &MeowError, verbose: &u8) {
+    error(&format!("    {}", err));
+    if let Some(hint) = err.hint() {
+        emit(Level::Hint, hint);
+    }
+    if let Some(path) = logpath() {
+        emit(
+            Level::Hint,
+            &format!("the full log of this run is in {}", path.display()),
+        );
+    }
+
+    debug(&format!("stage: {}", err.stage().name()), verbose);
+    debug(&format!("cause: {:?}", err.cause()), verbose);
+    if let MeowError::Command {
+        command,
+        exitcode,
+        stdout,
+        stderr,
+        ..
+    } = err
+    {
+        debug(&format!("command: {}", command), verbose);
+        debug(&format!("exit code: {:?}", exitcode), verbose);
+        for line in stdout.lines() {
+            debug(&format!("stdout: {}", line), verbose);
+        }
+        for line in stderr.lines() {
+            debug(&format!("stderr: {}", line), verbose);
+        }
+    }
 }
 
 pub fn error(text: &str) {
-    let term = Term::stderr();
-    term.write_line(&format!("{}", style(text).red())).unwrap();
+    emit(Level::Error, text);
 }
 
 pub fn important(text: &str) {
-    println!("{}", style(text).cyan());
+    emit(Level::Important, text);
 }
 
 pub fn info(text: &str) {
-    println!("{}", style(text).magenta());
+    emit(Level::Info, text);
 }
 
+// debug and trace output is always logged, whatever the verbosity
 pub fn debug(text: &str, verbose: &u8) {
-    if verbose.to_owned() >= 1 {
-        println!("[DEBUG] {}", style(text).blue())
+    if *verbose >= 1 {
+        emit(Level::Debug, text);
+    } else {
+        writelog(Level::Debug, text);
+    }
+}
+
+pub fn trace(text: &str, verbose: &u8) {
+    if *verbose >= 2 {
+        emit(Level::Trace, text);
+    } else {
+        writelog(Level::Trace, text);
     }
 }
 
+/// info that a warning or prompt depends on, so it's shown even with -q
+pub fn detail(text: &str) {
+    emit(Level::Plain, &paint(Level::Info, text));
+}
+
 pub fn success(text: &str) {
-    println!("{}", style(text).green());
+    emit(Level::Success, text);
 }
@@ -1,25 +1,120 @@
-use crate::{args::Args, loggers::*};
-use clap::{CommandFactory, Parser};
-use console::{Emoji, style};
-use homedir::my_home;
+use crate::{
+    args::{
+        Args, Command, CommitArgs, Invocation, PushArgs, RemoteCommand, RepoArgs, StageArgs,
+        TagArgs, replacelegacyflags,
+    },
+    completions::{printcompletions, printmanpage, writemanpages},
+    help::printhelp,
+};
+use clap::CommandFactory;
+use meow::{
+    MeowResult, PipelineReport, Stage,
+    aliases::expandaliases,
+    changelog::{UNRELEASED, writechangelog},
+    config::loadconfig,
+    getcleanroot, getrootdir,
+    git::{addremote, removeremote, restore, unstage},
+    logfile::openlog,
+    loggers::*,
+    multirepo::{MultiOptions, RepoSource, findrepos, runmulti},
+    pipeline::{Pipeline, PushOptions, TagOptions},
+    release::planrelease,
+    theme::configureoutput,
+    trust::trustedstages,
+};
 use std::{
-    io::{Error, ErrorKind},
-    path::{Path, PathBuf},
-    process::{Command, Output, exit},
-    str,
+    collections::BTreeMap,
+    ffi::OsString,
+    process::exit,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
+    time::Instant,
 };
 
 mod args;
-mod loggers;
+mod completions;
+mod help;
+
+// what each repository runs in multi-repo mode
+#[derive(Default)]
+struct RepoPlan<'a> {
+    status: bool,
+    stage: bool,
+    commit: bool,
+    files: Option<&'a [String]>,
+    message: &'a str,
+    allowsecrets: bool,
+    submodules: bool,
+    push: Option<&'a PushArgs>,
+    stoponerror: bool,
+}
+
+// the repository options and plan of the command, if it was asked to run in several
+fn multirepoplan(args: &Args) -> Option<(&RepoArgs, RepoPlan<'_>)> {
+    let (repos, mut plan) = match &args.command {
+        None => (
+            &args.repos,
+            RepoPlan {
+                status: false,
+                stage: true,
+                commit: true,
+                files: args.stage.add.as_deref(),
+                message: args.commitmessage.as_deref().unwrap_or_default(),
+                allowsecrets: args.commit.allowsecrets,
+                submodules: args.commit.recursesubmodules,
+                push: Some(&args.push),
+                ..Default::default()
+            },
+        ),
+        Some(Command::Stage { files, repos, .. }) => (
+            repos,
+            RepoPlan {
+                stage: true,
+                files: (!files.is_empty()).then_some(files.as_slice()),
+                ..Default::default()
+            },
+        ),
+        Some(Command::Commit {
+            stage,
+            message,
+            commit,
+            repos,
+            ..
+        }) => (
+            repos,
+            RepoPlan {
+                stage: stage.add.is_some(),
+                commit: true,
+                files: stage.add.as_deref(),
+                message,
+                allowsecrets: commit.allowsecrets,
+                submodules: commit.recursesubmodules,
+                ..Default::default()
+            },
+        ),
+        Some(Command::Push { push, repos }) => (
+            repos,
+            RepoPlan {
+                push: Some(push),
+                ..Default::default()
+            },
+        ),
+        Some(Command::Status { repos }) => (
+            repos,
+            RepoPlan {
+                status: true,
+                ..Default::default()
+            },
+        ),
+        Some(_) => return None,
+    };
+    plan.stoponerror = args.exitonerror;
+    (repos.repos.is_some() || repos.allrepos).then_some((repos, plan))
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut runstagepipeline = true;
-    let mut runcommitpipeline = true;
-    let mut runpushpipeline = true;
     let interrupted = Arc::new(AtomicBool::new(false));
     let i = interrupted.clone();
 
@@ -28,8 +123,32 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         i.store(true, Ordering::SeqCst);
     })?;
 
-    let args = match Args::try_parse() {
-        Ok(p) => p,
+    // aliases and output settings are needed before the arguments are parsed.
+    // a broken config is reported once the repository is known
+    let earlyconfig = loadconfig(getrootdir().ok().as_deref()).unwrap_or_default();
+    configureoutput(None, &earlyconfig.output, &earlyconfig.theme, false);
+    let aliases = &earlyconfig.aliases;
+
+    let argv = match expandargv(aliases) {
+        Ok(a) => a,
+        Err(e) => {
+            important("\nmeow");
+            important(&format!("version {}\n", env!("CARGO_PKG_VERSION")));
+            printerror(&e, &0);
+            exit(e.exitcode());
+        }
+    };
+
+    let (argv, replaced) = replacelegacyflags(argv);
+
+    let args = match Args::parsecommandline(argv) {
+        Ok(Invocation::Run(p)) => p,
+        Ok(Invocation::Help { path, long }) => {
+            important("\nmeow");
+            important(&format!("version {}\n", env!("CARGO_PKG_VERSION")));
+            printhelp(&path, long, aliases);
+            return Ok(());
+        }
         Err(err) => {
             important("\nmeow");
             important(&format!("version {}\n", env!("CARGO_PKG_VERSION")));
@@ -38,7 +157,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut usage = Args::command().render_usage().to_string();
 
             usage = String::from(usage.strip_prefix("Usage: ").unwrap());
-            usage = String::from(usage.strip_prefix(&format!("{}", commandname)).unwrap());
+            usage = String::from(usage.strip_prefix(commandname.as_str()).unwrap());
 
             let erroroutput = format!("{}", err);
             let errormsg = if let Some((before, _)) = erroroutput.split_once("\n\n") {
@@ -47,25 +166,50 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &erroroutput
             };
 
-            match err.kind() {
-                _ => println!("{}\n", style(errormsg).red()),
-            }
+            println!("{}\n", paint(Level::Error, errormsg));
 
-            println!("{}", style("usage: ").cyan());
-            print!("{}", style(&commandname).magenta());
-            println!("{}", style(usage).magenta().dim());
+            println!("{}", paint(Level::Important, "usage: "));
+            print!("{}", paint(Level::Info, &commandname));
+            println!("{}", console::style(paint(Level::Info, &usage)).dim());
 
             exit(1);
         }
     };
 
+    configureoutput(
+        args.color,
+        &earlyconfig.output,
+        &earlyconfig.theme,
+        args.ascii,
+    );
+    setverbosity(args.verbose, args.quiet);
+    if args.log || earlyconfig.log.enabled {
+        match openlog(&earlyconfig.log) {
+            Ok(path) => debug(&format!("logging to {}", path.display()), &args.verbose),
+            Err(e) => printerror(&e, &args.verbose),
+        }
+    }
+
+    // data output, so no banner
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            printcompletions(*shell)?;
+            return Ok(());
+        }
+        Some(Command::Man { dir }) => {
+            match dir {
+                Some(dir) => writemanpages(dir)?,
+                None => printmanpage()?,
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
+
     let verbose = args.verbose;
     let run = args.run;
     debug("initializing flags", &verbose);
-    let remoteadd = args.addremote;
-    let remoteremove = args.removeremote;
     let dryrun = args.dryrun;
-    let force = args.force;
     let exitonerror = args.exitonerror;
 
     if args.meow {
@@ -76,36 +220,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     important("\nmeow");
     important(&format!("version {}\n", env!("CARGO_PKG_VERSION")));
 
+    if let Some((old, suggestion)) = replaced {
+        emit(
+            Level::Hint,
+            &format!(
+                "{} is deprecated and will be removed, use `{}` instead\n",
+                old, suggestion
+            ),
+        );
+    }
+
     if run {
         debug("run flag was specified, hijacking pipeline", &verbose);
         error("run is not implemented yet.");
         return Ok(());
     }
 
-    debug("checking if help flag was specified", &verbose);
-    if args.help {
-        printhelp();
-        debug("help printed, exiting", &verbose);
-        return Ok(());
+    debug("checking if multi-repo mode was specified", &verbose);
+    if let Some((repos, plan)) = multirepoplan(&args) {
+        return runrepos(repos, &plan, &dryrun, &verbose);
     }
 
     debug("getting repository root", &verbose);
     let reporoot = match getrootdir() {
         Ok(r) => r,
         Err(e) => {
-            let errorstr = e.to_string();
-
-            if errorstr.contains("not a git repository") {
-                error("not a git repository. are you in the correct path?");
-            } else {
-                error(&errorstr);
-            }
-
-            if verbose > 0 {
-                return Err(Box::new(e));
-            } else {
-                exit(1);
-            }
+            printerror(&e, &verbose);
+            exit(e.exitcode());
         }
     };
 
@@ -118,423 +259,338 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     debug(&format!("root is {}", root), &verbose);
 
-    println!(
+    info(&format!(
         "{} {}\n",
-        style("repository root:").cyan(),
-        style(root).magenta()
-    );
+        paint(Level::Important, "repository root:"),
+        paint(Level::Info, &root)
+    ));
 
     debug("checking if version flag was specified", &verbose);
     if args.version {
         return Ok(());
     }
 
-    debug("checking if pushonly was specified", &verbose);
-    if args.pushonly {
-        debug("pushonly flag was specified", &verbose);
-        runstagepipeline = false;
-        runcommitpipeline = false;
-    }
-
-    debug("checking if commitonly was specified", &verbose);
-    if args.commitonly {
-        debug("commitonly flag was specified", &verbose);
-        runstagepipeline = false;
-        runpushpipeline = false;
-    }
-
-    debug("checking if stageonly was specified", &verbose);
-    if args.stageonly {
-        debug("stageonly flag was specified", &verbose);
-        runcommitpipeline = false;
-        runpushpipeline = false;
-    }
-
-    let message = match args.commitmessage {
-        Some(message) => message,
-        None => String::from(""),
+    debug("loading config", &verbose);
+    let config = match loadconfig(Some(&reporoot)) {
+        Ok(c) => c,
+        Err(e) => {
+            printerror(&e, &verbose);
+            exit(e.exitcode());
+        }
     };
 
     if dryrun {
         info("dry run\n");
     }
 
-    debug("checking if add remote was specified", &verbose);
-    if remoteadd.is_some() {
-        debug("add remote flag was specified", &verbose);
-        info("  EXPERIMENTAL: adding remote 'origin'...");
-        match addremote(
-            &reporoot,
-            "origin",
-            remoteadd.unwrap_or_default().as_str(),
-            &dryrun,
-            &verbose,
-        ) {
-            Ok(r) => r,
-            Err(e) => {
-                info("");
-                error(&e);
-                exit(1);
-            }
-        };
-        runstagepipeline = false;
-        runcommitpipeline = false;
-        runpushpipeline = false;
-    }
-
-    debug("checking if remove remote was specified", &verbose);
-    if remoteremove {
-        debug("remove remote flag was specified", &verbose);
-        info("  EXPERIMENTAL: removing remote 'origin'...");
-        match removeremote(&reporoot, "origin", &dryrun, &verbose) {
-            Ok(r) => r,
-            Err(e) => {
-                info("");
-                error(&e);
+    // only ask about the repository's stages when they could run
+    let runspipeline = matches!(
+        args.command,
+        None | Some(
+            Command::Stage { .. }
+                | Command::Commit { .. }
+                | Command::Push { .. }
+                | Command::Release { .. }
+        )
+    );
+    let stages = if runspipeline {
+        trustedstages(&reporoot, &config, &dryrun, &verbose)
+    } else {
+        &[]
+    };
+    let pipeline = Pipeline::new(&reporoot)
+        .customstages(stages)
+        .filesconfig(config.files.clone())
+        .branchesconfig(config.branches.clone())
+        .dryrun(dryrun)
+        .stoponerror(exitonerror)
+        .verbose(verbose);
+
+    let started = Instant::now();
+    match args.command {
+        None => {
+            debug(
+                "no command was specified, running the full pipeline",
+                &verbose,
+            );
+            let message = args.commitmessage.unwrap_or_default();
+            let pipeline = withstage(pipeline, args.stage);
+            let pipeline = withcommit(pipeline, message, &args.commit, true);
+            let pipeline = withtag(pipeline, args.tag);
+            runpipeline(withpush(pipeline, args.push), &dryrun);
+        }
+        Some(Command::Stage {
+            files, interactive, ..
+        }) => {
+            let add = (!files.is_empty()).then_some(files);
+            runpipeline(withstage(pipeline, StageArgs { add, interactive }), &dryrun);
+        }
+        Some(Command::Commit {
+            stage,
+            message,
+            commit,
+            tag,
+            ..
+        }) => {
+            let staging = stage.add.is_some() || stage.interactive;
+            let pipeline = if staging {
+                withstage(pipeline, stage)
+            } else {
+                debug(
+                    "no files were specified, committing what's staged",
+                    &verbose,
+                );
+                pipeline
+            };
+            let pipeline = withcommit(pipeline, message, &commit, staging);
+            runpipeline(withtag(pipeline, tag), &dryrun);
+        }
+        Some(Command::Push { push, .. }) => {
+            runpipeline(withpush(pipeline, push), &dryrun);
+        }
+        Some(Command::Status { .. }) => {
+            let cwd = std::env::current_dir()?;
+            let plan = RepoPlan {
+                status: true,
+                ..Default::default()
+            };
+            let opts = multioptions(&plan, dryrun);
+            if !runmulti(&[reporoot], &opts, 1, &cwd) {
                 exit(1);
             }
-        };
-        runstagepipeline = false;
-        runcommitpipeline = false;
-        runpushpipeline = false;
-    }
-
-    if runstagepipeline {
-        info("staging changes...");
-        debug("checking if files were specified to be staged", &verbose);
-        match args.add {
-            Some(toadd) => match stage(&reporoot, &toadd, &dryrun, &verbose) {
-                Err(e) => {
-                    error(&e);
-                    if exitonerror {
-                        exit(1);
-                    }
-                }
-                _ => (),
-            },
-            None => match stageall(&reporoot, &dryrun, &verbose) {
-                Err(e) => {
-                    error(&e);
-                    if exitonerror {
-                        exit(1);
-                    }
-                }
-                _ => (),
-            },
         }
-        success("done");
-    }
-
-    if runcommitpipeline {
-        info("\ncommitting...");
-        match commit(&reporoot, &message, &dryrun, &verbose) {
-            Err(e) => {
-                error(&e);
-                if exitonerror {
-                    exit(1);
-                }
-            }
-            _ => (),
+        Some(Command::Unstage { files }) => {
+            info("unstaging changes...");
+            finish(
+                Stage::Unstage,
+                started,
+                unstage(&reporoot, &files, &dryrun, &verbose),
+                &dryrun,
+                &verbose,
+            );
         }
-        success("done");
-    }
-
-    if runpushpipeline {
-        info("\npushing...");
-        match push(
-            &reporoot,
-            args.upstream.as_deref(),
-            &dryrun,
-            &force,
-            &verbose,
-        ) {
-            Err(e) => {
-                error(&e);
-                if exitonerror {
-                    exit(1);
-                }
-            }
-            _ => (),
+        Some(Command::Restore { files }) => {
+            info("restoring files...");
+            finish(
+                Stage::Restore,
+                started,
+                restore(&reporoot, &files, &dryrun, &verbose),
+                &dryrun,
+                &verbose,
+            );
         }
+        Some(Command::Remote(RemoteCommand::Add { name, url })) => {
+            info(&format!("adding remote '{}'...", name));
+            finish(
+                Stage::Remote,
+                started,
+                addremote(&reporoot, &name, &url, &dryrun, &verbose),
+                &dryrun,
+                &verbose,
+            );
+        }
+        Some(Command::Remote(RemoteCommand::Remove { name })) => {
+            info(&format!("removing remote '{}'...", name));
+            finish(
+                Stage::Remote,
+                started,
+                removeremote(&reporoot, &name, &dryrun, &verbose),
+                &dryrun,
+                &verbose,
+            );
+        }
+        Some(Command::Release {
+            bump,
+            changelog,
+            upstream,
+        }) => match planrelease(&reporoot, &config, bump, changelog, &verbose) {
+            Ok(release) => runpipeline(release.pipeline(pipeline, upstream), &dryrun),
+            Err(e) => finish(Stage::Release, started, Err(e), &dryrun, &verbose),
+        },
+        Some(Command::Changelog { from, to }) => {
+            info("updating changelog...");
+            let title = if to == "HEAD" {
+                UNRELEASED
+            } else {
+                to.as_str()
+            };
+            finish(
+                Stage::Changelog,
+                started,
+                writechangelog(
+                    &reporoot,
+                    from.as_deref(),
+                    &to,
+                    &config.release.tagprefix,
+                    title,
+                    &dryrun,
+                    &verbose,
+                ),
+                &dryrun,
+                &verbose,
+            );
+        }
+        Some(Command::Completions { .. } | Command::Man { .. }) => unreachable!(),
     }
-    success("done");
-
-    if dryrun {
-        info("\ndry run complete");
-        return Ok(());
-    }
-
-    info(&format!("{}", Emoji("\n😼", "\n>:3")));
     Ok(())
 }
 
-fn getrootdir() -> Result<PathBuf, std::io::Error> {
-    // git rev-parse --show-toplevel
-    let mut command = Command::new("git");
-    command.arg("rev-parse").arg("--show-toplevel");
-
-    let output = command.output()?;
-
-    if output.status.success() {
-        let stdout = str::from_utf8(&output.stdout).map_err(|e| {
-            Error::new(
-                ErrorKind::InvalidData,
-                format!("invalid utf-8 in git output: {}", e),
-            )
-        })?;
-        let root = PathBuf::from(stdout.trim());
-        Ok(root)
-    } else {
-        let stderr = str::from_utf8(&output.stderr).map_err(|e| {
-            Error::new(
-                ErrorKind::InvalidData,
-                format!("invalid utf-8 in git error output: {}", e),
-            )
-        })?;
-        Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "not a git repository or failed to find toplevel: {}",
-                stderr
-            ),
-        ))
-    }
-}
-
-fn getcleanroot(reporoot: &PathBuf) -> Result<String, Box<dyn std::error::Error>> {
-    let homediropt = my_home()?;
-
-    let cleanroot = if let Some(homedir) = homediropt {
-        if reporoot.starts_with(&homedir) {
-            let relpath = reporoot.strip_prefix(&homedir)?;
-            format!("~/{}", relpath.display())
-        } else {
-            reporoot.to_string_lossy().into_owned()
-        }
-    } else {
-        reporoot.to_string_lossy().into_owned()
+// expands a config alias at the start of the command line. arguments that
+// aren't valid unicode can't be matched against aliases, so they pass through
+fn expandargv(aliases: &BTreeMap<String, String>) -> MeowResult<Vec<OsString>> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let Some(utf8) = argv
+        .iter()
+        .map(|a| a.to_str().map(String::from))
+        .collect::<Option<Vec<String>>>()
+    else {
+        return Ok(argv);
     };
 
-    Ok(cleanroot)
+    let builtins = Args::commandnames();
+    let builtins: Vec<&str> = builtins.iter().map(String::as_str).collect();
+    Ok(expandaliases(utf8, aliases, &builtins)?
+        .into_iter()
+        .map(OsString::from)
+        .collect())
 }
 
-fn createcommand<'a>(args: &[&'a str]) -> Vec<&'a str> {
-    let mut command = vec!["git"];
-    command.extend(args);
-
-    command
+fn withstage(pipeline: Pipeline, stage: StageArgs) -> Pipeline {
+    match stage.add {
+        Some(toadd) if stage.interactive => pipeline.stagehunks(toadd),
+        None if stage.interactive => pipeline.stagehunks(Vec::new()),
+        Some(toadd) => pipeline.stagefiles(toadd),
+        None => pipeline.stageall(),
+    }
 }
 
-fn runcommand(repopath: &Path, args: &[&str]) -> Result<Output, String> {
-    let commandparts = createcommand(args);
-    printcommand(&commandparts);
-
-    if commandparts.is_empty() {
-        return Err("cannot execute an empty command.".to_string());
+// submodules are only swept up when meow is also staging
+fn withcommit(pipeline: Pipeline, message: String, commit: &CommitArgs, staging: bool) -> Pipeline {
+    let pipeline = pipeline.allowsecrets(commit.allowsecrets).commit(message);
+    if staging {
+        pipeline.submodules(commit.recursesubmodules)
+    } else {
+        pipeline
     }
+}
 
-    let command = commandparts[0];
-    let commandargs = &commandparts[1..];
-
-    let mut cmd = Command::new(command);
-    cmd.args(commandargs);
-    cmd.current_dir(repopath);
-
-    match cmd.output() {
-        Ok(o) => {
-            if o.status.success() {
-                Ok(o)
-            } else {
-                let stderr = str::from_utf8(&o.stderr)
-                    .unwrap_or("failed to read stderr (non-utf8)")
-                    .trim();
-                Err(format!(
-                    "command `{}` executed in `{}` failed with: {}",
-                    style(commandparts.join(" ")).yellow(),
-                    repopath.display(),
-                    style(stderr).red()
-                ))
-            }
-        }
-        Err(e) => Err(format!(
-            "failed to execute command `{}` in directory `{}`: {}",
-            style(commandparts.join(" ")).yellow(),
-            repopath.display(),
-            style(e.to_string()).red()
-        )),
+fn withtag(pipeline: Pipeline, tag: TagArgs) -> Pipeline {
+    match tag.tag {
+        Some(name) => pipeline.tag(TagOptions {
+            name,
+            message: tag.tagmessage,
+            sign: tag.signtag,
+            force: tag.forcetag,
+        }),
+        None => pipeline,
     }
 }
 
-fn stageall(repopath: &Path, dryrun: &bool, verbose: &u8) -> Result<(), String> {
-    debug("no files were specified, staging all", verbose);
-    let args = &["add", "."];
+fn withpush(pipeline: Pipeline, push: PushArgs) -> Pipeline {
+    pipeline
+        .forceprotected(push.forceprotected)
+        .pushwith(PushOptions {
+            upstream: push.upstream,
+            force: push.force,
+            ifincludes: push.ifincludes,
+        })
+}
 
-    if *dryrun {
-        debug("debug was specified, not staging", verbose);
-        printcommand(&args.to_vec());
-        return Ok(());
-    }
-
-    match runcommand(repopath, args) {
-        Ok(o) => {
-            printcommandoutput(o);
-            Ok(())
-        }
-        Err(e) => {
-            debug(&format!("error: {}", e), verbose);
-            Err(String::from("could not stage all"))
-        }
+// exits with the first failure's code, even if the pipeline carried on past it
+fn runpipeline(pipeline: Pipeline, dryrun: &bool) {
+    let report = pipeline.run();
+    report.printsummary();
+    if let Some(e) = report.firsterror() {
+        exit(e.exitcode());
     }
+    signoff(dryrun);
 }
 
-fn stage(repopath: &Path, files: &[String], dryrun: &bool, verbose: &u8) -> Result<(), String> {
-    debug(&format!("files {:#?} were specified", files), verbose);
-    let mut args = vec!["add".to_owned()];
-    args.extend(files.iter().cloned());
-
-    if *dryrun {
-        debug("debug was specified, not staging", verbose);
-        printcommand(&args.iter().map(|a| a.as_str()).collect::<Vec<&str>>());
-        return Ok(());
+// reports an operation that runs outside the pipeline, timed from `started`
+fn finish(stage: Stage, started: Instant, result: MeowResult<()>, dryrun: &bool, verbose: &u8) {
+    if let Err(e) = &result {
+        info("");
+        printerror(e, verbose);
     }
-
-    match runcommand(
-        repopath,
-        &args.iter().map(|a| a.as_str()).collect::<Vec<&str>>(),
-    ) {
-        Ok(o) => {
-            printcommandoutput(o);
-            Ok(())
-        }
-        Err(e) => {
-            if e.contains("did not match any files") {
-                debug(&format!("error: {}", e), verbose);
-                Err(String::from("    could not stage files: files not found"))
-            } else {
-                Err(String::from("    could not stage files"))
-            }
-        }
+    let report = PipelineReport::single(stage, result, started.elapsed());
+    report.printsummary();
+    if let Some(e) = report.firsterror() {
+        exit(e.exitcode());
     }
+    signoff(dryrun);
 }
 
-fn commit(repopath: &Path, message: &str, dryrun: &bool, verbose: &u8) -> Result<(), String> {
-    let args = &["commit", "-m", message];
-
+fn signoff(dryrun: &bool) {
     if *dryrun {
-        debug("dry run was specified, not committing", verbose);
-        printcommand(&args.to_vec());
-        return Ok(());
+        info("\ndry run complete");
+    } else {
+        info(emoji("\n😼", "\n>:3"));
     }
+}
 
-    match runcommand(repopath, args) {
-        Ok(o) => {
-            printcommitoutput(o, verbose);
-            Ok(())
-        }
-        Err(e) => {
-            debug(&format!("    error: {}", e), verbose);
-            Err(format!(
-                "    could not commit files. are there any changes to commit?"
-            ))
-        }
+fn multioptions<'a>(plan: &RepoPlan<'a>, dryrun: bool) -> MultiOptions<'a> {
+    MultiOptions {
+        status: plan.status,
+        stage: plan.stage,
+        commit: plan.commit,
+        push: plan.push.is_some(),
+        files: plan.files,
+        message: plan.message,
+        upstream: plan.push.and_then(|p| p.upstream.as_deref()),
+        force: plan.push.map_or(0, |p| p.force),
+        ifincludes: plan.push.is_some_and(|p| p.ifincludes),
+        forceprotected: plan.push.is_some_and(|p| p.forceprotected),
+        allowsecrets: plan.allowsecrets,
+        submodules: plan.submodules,
+        dryrun,
+        stoponerror: plan.stoponerror,
     }
 }
 
-fn push(
-    repopath: &Path,
-    upstream: Option<&str>,
+fn runrepos(
+    repos: &RepoArgs,
+    plan: &RepoPlan,
     dryrun: &bool,
-    force: &u8,
     verbose: &u8,
-) -> Result<(), String> {
-    let mut args = vec!["push"];
-    if let Some(upstreamval) = upstream {
-        debug(&format!("upstream {} was specified", upstreamval), verbose);
-        args.extend(["--set-upstream", "origin", upstreamval]);
-    }
-    if force.to_owned() == 1 {
-        debug("force was specified, using force-with-lease", verbose);
-        args.extend(["--force-with-lease"])
-    }
-    if force.to_owned() >= 2 {
-        debug("force was specified twice, using force", verbose);
-        args.extend(["--force"])
-    }
-
-    if *dryrun {
-        debug("dry run was specified, not pushing", verbose);
-        printcommand(&args);
-        return Ok(());
-    }
-
-    debug("dry run was not specified, pushing", verbose);
-    match runcommand(repopath, &args) {
-        Ok(o) => {
-            printpushoutput(o, verbose);
-            Ok(())
-        }
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = match repos.repos.as_deref() {
+        Some("") => RepoSource::Config,
+        Some(pattern) => RepoSource::Glob(pattern),
+        None => RepoSource::Discover,
+    };
+    let cwd = std::env::current_dir()?;
+    let config = match loadconfig(Some(&cwd)) {
+        Ok(c) => c,
         Err(e) => {
-            debug(&format!("error: {}", e), verbose);
-            Err(String::from("could not push to remote"))
+            printerror(&e, verbose);
+            exit(e.exitcode());
         }
-    }
-}
-
-fn addremote(
-    repopath: &Path,
-    remotename: &str,
-    remoteurl: &str,
-    dryrun: &bool,
-    verbose: &u8,
-) -> Result<(), String> {
-    let args = vec!["remote", "add", remotename, remoteurl];
-
-    if *dryrun {
-        debug("dry run was specified, not adding remote", verbose);
-        printcommand(&args);
-        return Ok(());
-    }
+    };
 
-    debug("dry run was not specified, adding remote", verbose);
-    match runcommand(repopath, &args) {
-        Ok(o) => {
-            printcommandoutput(o);
-            Ok(())
+    let found = match findrepos(&source, &config, &cwd) {
+        Ok(r) if r.is_empty() => {
+            error("no git repositories found");
+            exit(1);
         }
+        Ok(r) => r,
         Err(e) => {
-            debug(&format!("error: {}", e), verbose);
-            if e.contains("[<options>]") {
-                Err("could not add remote: url not specified".to_string())
-            } else {
-                Err("could not add remote".to_string())
-            }
+            printerror(&e, verbose);
+            exit(e.exitcode());
         }
-    }
-}
-
-fn removeremote(
-    repopath: &Path,
-    remotename: &str,
-    dryrun: &bool,
-    verbose: &u8,
-) -> Result<(), String> {
-    let args = vec!["remote", "remove", remotename];
+    };
 
     if *dryrun {
-        debug("dry run was specified, not adding remote", verbose);
-        printcommand(&args);
-        return Ok(());
+        info("dry run\n");
     }
-
-    debug("dry run was not specified, adding remote", verbose);
-    match runcommand(repopath, &args) {
-        Ok(o) => {
-            printcommandoutput(o);
-            Ok(())
-        }
-        Err(e) => {
-            debug(&format!("error: {}", e), verbose);
-            Err(String::from("could not add remote"))
-        }
+    info(&format!(
+        "{} {} repositories...\n",
+        if plan.status { "checking" } else { "updating" },
+        found.len()
+    ));
+    let opts = multioptions(plan, *dryrun);
+    if !runmulti(&found, &opts, repos.jobs, &cwd) {
+        exit(1);
     }
+    info(emoji("\n😼", "\n>:3"));
+    Ok(())
 }
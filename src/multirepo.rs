@@ -0,0 +1,469 @@
+use crate::{
+    config::{Config, loadconfig},
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::gitoutput,
+    loggers::*,
+    pipeline::{Outcome, Pipeline, PipelineReport, PushOptions},
+    trust::trustedstages,
+};
+use console::measure_text_width;
+use homedir::my_home;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+// directories that never contain repos worth operating on
+const SKIPDIRS: &[&str] = &["target", "node_modules", "vendor", "__pycache__"];
+
+pub enum RepoSource<'a> {
+    Config,
+    Glob(&'a str),
+    Discover,
+}
+
+pub struct MultiOptions<'a> {
+    pub status: bool,
+    pub stage: bool,
+    pub commit: bool,
+    pub push: bool,
+    pub files: Option<&'a [String]>,
+    pub message: &'a str,
+    pub upstream: Option<&'a str>,
+    pub force: u8,
+    pub ifincludes: bool,
+    pub forceprotected: bool,
+    pub allowsecrets: bool,
+    /// commit and push changed submodules first, without asking
+    pub submodules: bool,
+    pub dryrun: bool,
+    pub stoponerror: bool,
+}
+
+pub enum StageResult {
+    Ok(String),
+    Skipped(String),
+    Failed,
+}
+
+pub struct RepoResult {
+    pub path: PathBuf,
+    pub columns: Vec<StageResult>,
+    pub failed: bool,
+    /// what the repo printed, held back while the repos ran
+    pub output: Vec<(Level, String)>,
+}
+
+fn expandhome(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/")
+        && let Ok(Some(home)) = my_home()
+    {
+        return home.join(rest);
+    }
+    PathBuf::from(path)
+}
+
+fn repotoplevel(path: &Path) -> Option<PathBuf> {
+    gitoutput(path, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .map(|root| PathBuf::from(root.trim()))
+}
+
+fn discover(dir: &Path, found: &mut Vec<PathBuf>) {
+    if dir.join(".git").exists() {
+        found.push(dir.to_path_buf());
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let isdir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+        if isdir && !name.starts_with('.') && !SKIPDIRS.contains(&name.as_ref()) {
+            discover(&path, found);
+        }
+    }
+}
+
+pub fn findrepos(source: &RepoSource, config: &Config, cwd: &Path) -> MeowResult<Vec<PathBuf>> {
+    let candidates = match source {
+        RepoSource::Config => {
+            if config.repos.paths.is_empty() {
+                return Err(MeowError::pipeline(
+                    Stage::Setup,
+                    Cause::InvalidConfig,
+                    "no repos configured. add `paths` under [repos], or pass a glob to --repos",
+                ));
+            }
+            config
+                .repos
+                .paths
+                .iter()
+                .map(|p| cwd.join(expandhome(p)))
+                .collect()
+        }
+        RepoSource::Glob(pattern) => {
+            let pattern = cwd.join(expandhome(pattern));
+            glob::glob(&pattern.to_string_lossy())
+                .map_err(|e| {
+                    MeowError::pipeline(
+                        Stage::Setup,
+                        Cause::Unknown,
+                        format!("invalid glob pattern: {}", e),
+                    )
+                })?
+                .flatten()
+                .filter(|p| p.is_dir())
+                .collect()
+        }
+        RepoSource::Discover => {
+            let mut found = Vec::new();
+            discover(cwd, &mut found);
+            found
+        }
+    };
+
+    let mut repos = BTreeSet::new();
+    for candidate in candidates {
+        match repotoplevel(&candidate) {
+            Some(root) => {
+                repos.insert(root);
+            }
+            None => error(&format!(
+                "skipping {}: not a git repository",
+                candidate.display()
+            )),
+        }
+    }
+
+    Ok(repos.into_iter().collect())
+}
+
+// the same pipeline a single repository runs, built from the repo's own config
+fn repopipeline(repo: &Path, config: &Config, opts: &MultiOptions) -> Pipeline {
+    let mut pipeline = Pipeline::new(repo)
+        .customstages(&config.stages)
+        .filesconfig(config.files.clone())
+        .branchesconfig(config.branches.clone())
+        .allowsecrets(opts.allowsecrets)
+        .forceprotected(opts.forceprotected)
+        .dryrun(opts.dryrun)
+        .stoponerror(opts.stoponerror)
+        .verbose(verbosity());
+    if opts.stage {
+        pipeline = match opts.files {
+            Some(files) => pipeline.stagefiles(files.to_vec()),
+            None => pipeline.stageall(),
+        };
+    }
+    if opts.commit {
+        pipeline = pipeline.commit(opts.message);
+        // repos run in parallel, so there's no asking about each submodule
+        if opts.stage && opts.submodules {
+            pipeline = pipeline.submodules(true);
+        }
+    }
+    if opts.push {
+        pipeline = pipeline.pushwith(PushOptions {
+            upstream: opts.upstream.map(String::from),
+            force: opts.force,
+            ifincludes: opts.ifincludes,
+        });
+    }
+    pipeline
+}
+
+// loads the repo's config and asks its questions, before any repo runs. None
+// if the repo shouldn't run, with the reason already printed
+fn preparerepo(repo: &Path, opts: &MultiOptions, cwd: &Path) -> Option<Config> {
+    let prepared = loadconfig(Some(repo)).and_then(|mut config| {
+        if opts.status {
+            return Ok(config);
+        }
+        info(&format!("checking {}...", displayname(repo, cwd)));
+        let stages = trustedstages(repo, &config, &opts.dryrun, &verbosity()).to_vec();
+        config.stages = stages;
+        repopipeline(repo, &config, opts).confirm()?;
+        Ok(config)
+    });
+
+    match prepared {
+        Ok(config) => Some(config),
+        Err(e) => {
+            error(&format!("{}:", displayname(repo, cwd)));
+            printerror(&e, &verbosity());
+            None
+        }
+    }
+}
+
+// a table cell for one of the built-in stages
+fn stagecell(report: &PipelineReport, stage: Stage, wanted: bool) -> StageResult {
+    if !wanted {
+        return StageResult::Skipped(String::from("-"));
+    }
+    match report.outcome(stage) {
+        Some(Outcome::Succeeded) => StageResult::Ok(String::from("ok")),
+        Some(Outcome::Skipped(reason)) => StageResult::Skipped(reason.clone()),
+        Some(Outcome::Failed(e) | Outcome::Warned(e)) if *e.cause() == Cause::NothingToCommit => {
+            StageResult::Skipped(String::from("nothing to commit"))
+        }
+        Some(Outcome::Failed(_) | Outcome::Warned(_)) => StageResult::Failed,
+        None => StageResult::Skipped(String::from("-")),
+    }
+}
+
+fn reportcolumns(repo: &Path, report: &PipelineReport, opts: &MultiOptions) -> Vec<StageResult> {
+    let commit = match stagecell(report, Stage::Commit, opts.commit) {
+        StageResult::Ok(_) if opts.dryrun => StageResult::Ok(String::from("dry run")),
+        StageResult::Ok(_) => {
+            let hash = gitoutput(repo, &["rev-parse", "--short", "HEAD"]).unwrap_or_default();
+            StageResult::Ok(format!("committed {}", hash.trim()))
+        }
+        cell => cell,
+    };
+    vec![
+        stagecell(report, Stage::Add, opts.stage),
+        commit,
+        stagecell(report, Stage::Push, opts.push),
+    ]
+}
+
+// a repo with nothing new to commit hasn't failed
+fn reportfailed(report: &PipelineReport) -> bool {
+    report.steps.iter().any(|step| match &step.outcome {
+        Outcome::Failed(e) => *e.cause() != Cause::NothingToCommit,
+        _ => false,
+    })
+}
+
+fn runstatus(repo: &Path) -> Vec<StageResult> {
+    let output = match gitoutput(repo, &["status", "--porcelain=v2", "--branch"]) {
+        Ok(o) => o,
+        Err(e) => {
+            printerror(&e.withstage(Stage::Status), &verbosity());
+            return vec![StageResult::Failed];
+        }
+    };
+
+    let mut branch = String::from("(detached)");
+    let mut upstream = None;
+    let mut aheadbehind = None;
+    let mut changes = 0;
+
+    for line in output.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            branch = head.to_string();
+        } else if let Some(up) = line.strip_prefix("# branch.upstream ") {
+            upstream = Some(up.to_string());
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            let mut parts = ab.split_whitespace();
+            let ahead = parts.next().unwrap_or("+0").trim_start_matches('+');
+            let behind = parts.next().unwrap_or("-0").trim_start_matches('-');
+            aheadbehind = Some((ahead.to_string(), behind.to_string()));
+        } else if !line.starts_with('#') {
+            changes += 1;
+        }
+    }
+
+    vec![
+        StageResult::Ok(branch),
+        match upstream {
+            Some(up) => StageResult::Ok(up),
+            None => StageResult::Skipped(String::from("no upstream")),
+        },
+        match aheadbehind {
+            Some((ahead, behind)) if ahead == "0" && behind == "0" => {
+                StageResult::Ok(String::from("in sync"))
+            }
+            Some((ahead, behind)) => StageResult::Skipped(format!(
+                "{}{} {}{}",
+                symbol("↑", "+"),
+                ahead,
+                symbol("↓", "-"),
+                behind
+            )),
+            None => StageResult::Skipped(String::from("-")),
+        },
+        if changes == 0 {
+            StageResult::Ok(String::from("clean"))
+        } else {
+            StageResult::Skipped(format!("{} changed", changes))
+        },
+    ]
+}
+
+fn runrepo(repo: &Path, config: Option<&Config>, opts: &MultiOptions) -> RepoResult {
+    let ((columns, failed), output) = capture(|| {
+        if opts.status {
+            let columns = runstatus(repo);
+            let failed = columns.iter().any(|c| matches!(c, StageResult::Failed));
+            return (columns, failed);
+        }
+        let Some(config) = config else {
+            let notrun = || StageResult::Skipped(String::from("not run"));
+            return (vec![notrun(), notrun(), notrun()], true);
+        };
+
+        let report = repopipeline(repo, config, opts).unattended(true).run();
+        (reportcolumns(repo, &report, opts), reportfailed(&report))
+    });
+
+    RepoResult {
+        path: repo.to_path_buf(),
+        columns,
+        failed,
+        output,
+    }
+}
+
+fn displayname(repo: &Path, cwd: &Path) -> String {
+    match repo.strip_prefix(cwd) {
+        Ok(rel) if rel.as_os_str().is_empty() => String::from("."),
+        Ok(rel) => rel.display().to_string(),
+        Err(_) => repo.display().to_string(),
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(measure_text_width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+pub fn printtable(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(measure_text_width(cell));
+        }
+    }
+
+    let header = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            pad(
+                &console::style(paint(Level::Important, h))
+                    .bold()
+                    .to_string(),
+                widths[i],
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("  ");
+    emit(Level::Plain, &format!("  {}", header));
+
+    for row in rows {
+        let line = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| pad(cell, widths[i]))
+            .collect::<Vec<String>>()
+            .join("  ");
+        emit(Level::Plain, &format!("  {}", line));
+    }
+}
+
+fn rendercell(result: &StageResult) -> String {
+    match result {
+        StageResult::Ok(text) => paint(Level::Success, text),
+        StageResult::Skipped(text) => paint(Level::Hint, text),
+        StageResult::Failed => paint(Level::Error, "failed"),
+    }
+}
+
+/// runs the pipeline (or status) across `repos`, `jobs` at a time. anything that
+/// needs asking is asked first, one repo at a time. returns false if any repo failed
+pub fn runmulti(repos: &[PathBuf], opts: &MultiOptions, jobs: usize, cwd: &Path) -> bool {
+    let configs: Vec<Option<Config>> = repos
+        .iter()
+        .map(|repo| preparerepo(repo, opts, cwd))
+        .collect();
+
+    let jobs = jobs.clamp(1, repos.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<RepoResult>>> = Mutex::new(repos.iter().map(|_| None).collect());
+
+    let progress = ProgressBar::new(repos.len() as u64);
+    if quiet() {
+        progress.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    if let Ok(progressstyle) = ProgressStyle::with_template("  {spinner} {pos}/{len} {wide_msg}") {
+        let progressstyle = if asciimode() {
+            progressstyle.tick_chars("-\\|/ ")
+        } else {
+            progressstyle
+        };
+        progress.set_style(progressstyle);
+    }
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(repo) = repos.get(idx) else {
+                        break;
+                    };
+
+                    progress.set_message(displayname(repo, cwd));
+                    let result = runrepo(repo, configs[idx].as_ref(), opts);
+                    if let Ok(mut results) = results.lock() {
+                        results[idx] = Some(result);
+                    }
+                    progress.inc(1);
+                }
+            });
+        }
+    });
+    progress.finish_and_clear();
+
+    let results: Vec<RepoResult> = results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let headers: &[&str] = if opts.status {
+        &["repo", "branch", "upstream", "ahead/behind", "changes"]
+    } else {
+        &["repo", "stage", "commit", "push"]
+    };
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| {
+            let mut row = vec![displayname(&r.path, cwd)];
+            row.extend(r.columns.iter().map(rendercell));
+            row.resize(headers.len(), String::new());
+            row
+        })
+        .collect();
+    printtable(headers, &rows);
+
+    // the failed repos' own output says what went wrong
+    let failures: Vec<&RepoResult> = results.iter().filter(|r| r.failed).collect();
+    for failure in &failures {
+        if failure.output.is_empty() {
+            continue;
+        }
+        error(&format!("\n{}:", displayname(&failure.path, cwd)));
+        for (level, text) in &failure.output {
+            emit(*level, text);
+        }
+    }
+
+    failures.is_empty()
+}
@@ -0,0 +1,478 @@
+use crate::{
+    artifacts::checkartifacts,
+    branches::{BranchesConfig, guardbranches},
+    error::{MeowError, MeowResult, Stage},
+    forcepush::confirmforcepush,
+    largefiles::FilesConfig,
+    loggers::*,
+    multirepo::printtable,
+    stages::{
+        AddStage, Anchor, BranchStage, CommitStage, CustomStage, FilesStage, OnFailure,
+        PipelineStage, PushStage, SecretsStage, StageContext, SubmodulesStage, TagStage,
+    },
+};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+pub enum Files {
+    All,
+    Paths(Vec<String>),
+    /// pick hunks interactively, from these paths or every tracked file
+    Hunks(Vec<String>),
+}
+
+pub struct TagOptions {
+    pub name: String,
+    /// defaults to the commit message, or the tag name when there is none
+    pub message: Option<String>,
+    pub sign: bool,
+    pub force: bool,
+}
+
+pub struct PushOptions {
+    pub upstream: Option<String>,
+    pub force: u8,
+    pub ifincludes: bool,
+}
+
+pub enum Outcome {
+    Succeeded,
+    Skipped(String),
+    Failed(MeowError),
+    /// failed, but the stage's failure policy let the pipeline carry on
+    Warned(MeowError),
+}
+
+pub struct StepReport {
+    pub stage: Stage,
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+#[derive(Default)]
+pub struct PipelineReport {
+    pub steps: Vec<StepReport>,
+    /// a failing stage stopped the pipeline early
+    pub stopped: bool,
+}
+
+impl PipelineReport {
+    /// a report of a single operation run outside a pipeline, like `meow unstage`
+    pub fn single(stage: Stage, result: MeowResult<()>, duration: Duration) -> Self {
+        let mut report = PipelineReport::default();
+        let outcome = match result {
+            Ok(()) => Outcome::Succeeded,
+            Err(e) => Outcome::Failed(e),
+        };
+        report.record(stage, stage.name().to_string(), outcome, duration);
+        report
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.firsterror().is_none()
+    }
+
+    pub fn firsterror(&self) -> Option<&MeowError> {
+        self.steps.iter().find_map(|step| match &step.outcome {
+            Outcome::Failed(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    pub fn outcome(&self, stage: Stage) -> Option<&Outcome> {
+        self.steps
+            .iter()
+            .find(|step| step.stage == stage)
+            .map(|step| &step.outcome)
+    }
+
+    fn record(&mut self, stage: Stage, name: String, outcome: Outcome, duration: Duration) {
+        self.steps.push(StepReport {
+            stage,
+            name,
+            outcome,
+            duration,
+        });
+    }
+
+    /// prints each step, how it went and how long it took. with -q only a
+    /// failure gets it printed
+    pub fn printsummary(&self) {
+        let warned = self
+            .steps
+            .iter()
+            .any(|step| matches!(step.outcome, Outcome::Warned(_)));
+        if self.steps.is_empty() || (quiet() && self.succeeded() && !warned) {
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = self
+            .steps
+            .iter()
+            .map(|step| {
+                let result = match &step.outcome {
+                    Outcome::Succeeded => paint(Level::Success, "ok"),
+                    Outcome::Skipped(reason) => {
+                        paint(Level::Hint, &format!("skipped ({})", reason))
+                    }
+                    Outcome::Failed(_) => paint(Level::Error, "failed"),
+                    Outcome::Warned(_) => paint(Level::Hint, "failed, continued"),
+                };
+                vec![step.name.clone(), result, formatduration(step.duration)]
+            })
+            .collect();
+
+        important("\nsummary:");
+        printtable(&["step", "result", "time"], &rows);
+        if self.stopped {
+            error("stopped at the first failure");
+        }
+    }
+}
+
+fn formatduration(duration: Duration) -> String {
+    if duration.as_secs() == 0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+/// builds and runs the stage -> commit -> tag -> push pipeline for one repository,
+/// with any custom stages slotted in front of the built-in ones
+///
+/// ```no_run
+/// use meow::{getrootdir, pipeline::Pipeline};
+///
+/// let root = getrootdir().unwrap();
+/// let report = Pipeline::new(root).stageall().commit("fix typo").push().run();
+/// assert!(report.succeeded());
+/// ```
+pub struct Pipeline {
+    repo: PathBuf,
+    files: Option<Files>,
+    message: Option<String>,
+    tag: Option<TagOptions>,
+    push: Option<PushOptions>,
+    custom: Vec<(Anchor, Box<dyn PipelineStage>)>,
+    submodules: bool,
+    assumeyes: bool,
+    allowsecrets: bool,
+    filesconfig: FilesConfig,
+    branchesconfig: BranchesConfig,
+    forceprotected: bool,
+    dryrun: bool,
+    stoponerror: bool,
+    unattended: bool,
+    verbose: u8,
+}
+
+impl Pipeline {
+    pub fn new(repo: impl Into<PathBuf>) -> Pipeline {
+        Pipeline {
+            repo: repo.into(),
+            files: None,
+            message: None,
+            tag: None,
+            push: None,
+            custom: Vec::new(),
+            submodules: false,
+            assumeyes: false,
+            allowsecrets: false,
+            filesconfig: FilesConfig::default(),
+            branchesconfig: BranchesConfig::default(),
+            forceprotected: false,
+            dryrun: false,
+            stoponerror: false,
+            unattended: false,
+            verbose: 0,
+        }
+    }
+
+    pub fn stageall(mut self) -> Pipeline {
+        self.files = Some(Files::All);
+        self
+    }
+
+    pub fn stagefiles(mut self, files: Vec<String>) -> Pipeline {
+        self.files = Some(Files::Paths(files));
+        self
+    }
+
+    /// walks through the unstaged hunks and stages the ones the user picks
+    pub fn stagehunks(mut self, files: Vec<String>) -> Pipeline {
+        self.files = Some(Files::Hunks(files));
+        self
+    }
+
+    pub fn commit(mut self, message: impl Into<String>) -> Pipeline {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn tag(mut self, options: TagOptions) -> Pipeline {
+        self.tag = Some(options);
+        self
+    }
+
+    pub fn push(self) -> Pipeline {
+        self.pushwith(PushOptions {
+            upstream: None,
+            force: 0,
+            ifincludes: false,
+        })
+    }
+
+    pub fn pushwith(mut self, options: PushOptions) -> Pipeline {
+        self.push = Some(options);
+        self
+    }
+
+    /// offers to commit and push changed submodules before the parent
+    pub fn submodules(mut self, assumeyes: bool) -> Pipeline {
+        self.submodules = true;
+        self.assumeyes = assumeyes;
+        self
+    }
+
+    /// commit even if the staged changes look like they contain secrets
+    pub fn allowsecrets(mut self, allow: bool) -> Pipeline {
+        self.allowsecrets = allow;
+        self
+    }
+
+    /// size limit and policy for large or binary files found after staging
+    pub fn filesconfig(mut self, config: FilesConfig) -> Pipeline {
+        self.filesconfig = config;
+        self
+    }
+
+    /// branches that need confirmation to commit or push to
+    pub fn branchesconfig(mut self, config: BranchesConfig) -> Pipeline {
+        self.branchesconfig = config;
+        self
+    }
+
+    /// allow force pushes to protected branches (after confirmation)
+    pub fn forceprotected(mut self, force: bool) -> Pipeline {
+        self.forceprotected = force;
+        self
+    }
+
+    pub fn dryrun(mut self, dryrun: bool) -> Pipeline {
+        self.dryrun = dryrun;
+        self
+    }
+
+    /// stop at the first failing step instead of carrying on
+    pub fn stoponerror(mut self, stoponerror: bool) -> Pipeline {
+        self.stoponerror = stoponerror;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: u8) -> Pipeline {
+        self.verbose = verbose;
+        self
+    }
+
+    // runs without prompting, once `confirm` has asked what it could
+    pub(crate) fn unattended(mut self, unattended: bool) -> Pipeline {
+        self.unattended = unattended;
+        self
+    }
+
+    /// inserts a stage in front of one of the built-in ones. it only runs when
+    /// that built-in stage does
+    pub fn before(mut self, anchor: Anchor, stage: Box<dyn PipelineStage>) -> Pipeline {
+        self.custom.push((anchor, stage));
+        self
+    }
+
+    /// adds the custom stages from config
+    pub fn customstages(self, stages: &[CustomStage]) -> Pipeline {
+        stages.iter().fold(self, |pipeline, stage| {
+            pipeline.before(stage.before, Box::new(stage.clone()))
+        })
+    }
+
+    fn customat(&self, anchor: Anchor) -> impl Iterator<Item = &dyn PipelineStage> {
+        self.custom
+            .iter()
+            .filter(move |(a, _)| *a == anchor)
+            .map(|(_, stage)| stage.as_ref())
+    }
+
+    /// the ordered list of stages this pipeline will run
+    fn stages(&self) -> Vec<Box<dyn PipelineStage + '_>> {
+        let mut stages: Vec<Box<dyn PipelineStage + '_>> = Vec::new();
+        let custom = |anchor| {
+            self.customat(anchor)
+                .map(|stage| Box::new(Custom(stage)) as _)
+        };
+
+        // asked once, before anything touches the index, so declining leaves
+        // the repository as it was
+        if (self.message.is_some() || self.push.is_some()) && !self.unattended {
+            stages.push(Box::new(BranchStage {
+                config: &self.branchesconfig,
+                commit: self.message.is_some(),
+                push: self.push.as_ref(),
+                forceprotected: self.forceprotected,
+            }));
+        }
+
+        if let Some(files) = &self.files {
+            stages.extend(custom(Anchor::Stage));
+            if self.submodules
+                && let Some(message) = &self.message
+            {
+                stages.push(Box::new(SubmodulesStage {
+                    message,
+                    push: self.push.is_some(),
+                    allowsecrets: self.allowsecrets,
+                    assumeyes: self.assumeyes,
+                }));
+            }
+            stages.push(Box::new(AddStage {
+                files,
+                checkartifacts: !self.unattended,
+            }));
+            stages.push(Box::new(FilesStage {
+                config: &self.filesconfig,
+                unattended: self.unattended,
+            }));
+        }
+
+        if let Some(message) = &self.message {
+            stages.extend(custom(Anchor::Commit));
+            stages.push(Box::new(SecretsStage {
+                allow: self.allowsecrets,
+            }));
+            stages.push(Box::new(CommitStage { message }));
+        }
+
+        if let Some(options) = &self.tag
+            && (self.message.is_some() || self.push.is_some())
+        {
+            stages.push(Box::new(TagStage {
+                options,
+                message: self.message.as_deref(),
+            }));
+        }
+
+        if let Some(options) = &self.push {
+            stages.extend(custom(Anchor::Push));
+            stages.push(Box::new(PushStage {
+                options,
+                tag: self.tag.as_ref(),
+                unattended: self.unattended,
+            }));
+        }
+
+        stages
+    }
+
+    /// asks up front about build artifacts, protected branches and force pushes
+    pub fn confirm(&self) -> MeowResult<()> {
+        if matches!(self.files, Some(Files::All)) {
+            checkartifacts(&self.repo, &self.dryrun, &self.verbose)?;
+        }
+        if self.message.is_some() || self.push.is_some() {
+            guardbranches(
+                &self.repo,
+                &self.branchesconfig,
+                self.message.is_some(),
+                self.push
+                    .as_ref()
+                    .map(|options| (options.upstream.as_deref(), options.force)),
+                &self.forceprotected,
+                &self.dryrun,
+                &self.verbose,
+            )?;
+        }
+        if let Some(options) = &self.push {
+            confirmforcepush(
+                &self.repo,
+                options.upstream.as_deref(),
+                &options.force,
+                &self.dryrun,
+                &self.verbose,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn run(&self) -> PipelineReport {
+        let mut report = PipelineReport::default();
+
+        for stage in self.stages() {
+            // still listed, so the summary covers every stage that would have run
+            if report.stopped {
+                report.record(
+                    stage.stage(),
+                    stage.name(),
+                    Outcome::Skipped(String::from("an earlier stage failed")),
+                    Duration::ZERO,
+                );
+                continue;
+            }
+
+            let ctx = StageContext {
+                repo: &self.repo,
+                dryrun: self.dryrun,
+                verbose: self.verbose,
+                report: &report,
+            };
+            let name = stage.name();
+            let started = Instant::now();
+            let outcome = match stage.run(&ctx) {
+                Outcome::Failed(e) => match stage.onfailure() {
+                    None | Some(OnFailure::Abort) => {
+                        printerror(&e, &self.verbose);
+                        report.stopped = stage.onfailure().is_some() || self.stoponerror;
+                        Outcome::Failed(e)
+                    }
+                    Some(OnFailure::Warn) => {
+                        printerror(&e, &self.verbose);
+                        Outcome::Warned(e)
+                    }
+                    Some(OnFailure::Ignore) => {
+                        debug(
+                            &format!("ignoring failure of {}: {}", name, e),
+                            &self.verbose,
+                        );
+                        Outcome::Warned(e)
+                    }
+                },
+                outcome => outcome,
+            };
+            report.record(stage.stage(), name, outcome, started.elapsed());
+        }
+
+        report
+    }
+}
+
+// lets a borrowed custom stage sit in the same list as the owned built-ins
+struct Custom<'a>(&'a dyn PipelineStage);
+
+impl PipelineStage for Custom<'_> {
+    fn stage(&self) -> Stage {
+        self.0.stage()
+    }
+
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn onfailure(&self) -> Option<OnFailure> {
+        self.0.onfailure()
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        self.0.run(ctx)
+    }
+}
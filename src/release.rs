@@ -0,0 +1,575 @@
+use crate::{
+    changelog::{CHANGELOGFILE, writechangelog},
+    config::Config,
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::gitoutput,
+    loggers::*,
+    pipeline::{Outcome, Pipeline, PushOptions, TagOptions},
+    stages::{Anchor, PipelineStage, StageContext},
+};
+use clap::ValueEnum;
+use std::{fmt, fs, path::Path};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Auto,
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn parse(s: &str) -> Option<Version> {
+        let s = s.trim().trim_start_matches('v');
+        // pre-release and build metadata are dropped, the next release is always a plain version
+        let core = s.split(['-', '+']).next()?;
+        let mut parts = core.split('.');
+        let version = Version {
+            major: parts.next()?.parse().ok()?,
+            minor: parts.next()?.parse().ok()?,
+            patch: parts.next()?.parse().ok()?,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(version)
+    }
+
+    pub fn bump(self, bump: Bump) -> Version {
+        match bump {
+            Bump::Major => Version {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Version {
+                major: self.major,
+                minor: self.minor + 1,
+                patch: 0,
+            },
+            Bump::Patch | Bump::Auto => Version {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl fmt::Display for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Bump::Auto => "auto",
+            Bump::Patch => "patch",
+            Bump::Minor => "minor",
+            Bump::Major => "major",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// the most recent tag reachable from `rev` that starts with `prefix`, so
+/// other tags like `docs-1` aren't taken for releases
+pub fn lasttag(repopath: &Path, rev: &str, prefix: &str) -> Option<String> {
+    let pattern = format!("{}*", prefix);
+    gitoutput(
+        repopath,
+        &["describe", "--tags", "--abbrev=0", "--match", &pattern, rev],
+    )
+    .ok()
+    .map(|t| t.trim().to_string())
+    .filter(|t| !t.is_empty())
+}
+
+/// full commit messages (subject and body) in `range`, newest first
+pub fn commitmessages(repopath: &Path, range: &str) -> MeowResult<Vec<String>> {
+    let stdout = gitoutput(repopath, &["log", "--format=%B%x1e", range])
+        .map_err(|e| e.withstage(Stage::Release))?;
+    Ok(stdout
+        .split('\u{1e}')
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .collect())
+}
+
+/// the conventional commit type of a subject line, eg `feat` for `feat(cli)!: ...`
+pub fn committype(subject: &str) -> Option<(&str, bool)> {
+    let (header, _) = subject.split_once(':')?;
+    let breaking = header.ends_with('!');
+    let header = header.trim_end_matches('!');
+    let committype = header.split('(').next()?.trim();
+
+    if committype.is_empty() || committype.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((committype, breaking))
+}
+
+pub fn detectbump(messages: &[String]) -> Bump {
+    let mut bump = Bump::Patch;
+
+    for message in messages {
+        let subject = message.lines().next().unwrap_or("");
+        let breakingfooter =
+            message.contains("BREAKING CHANGE:") || message.contains("BREAKING-CHANGE:");
+
+        match committype(subject) {
+            _ if breakingfooter => return Bump::Major,
+            Some((_, true)) => return Bump::Major,
+            Some(("feat", false)) => bump = Bump::Minor,
+            _ => (),
+        }
+    }
+
+    bump
+}
+
+/// replaces the first `version` line holding `old` with `new`, leaving the rest of the file untouched
+fn replaceversion(contents: &str, old: &str, new: &str) -> Option<String> {
+    let mut replaced = false;
+    let mut result = String::with_capacity(contents.len());
+
+    for line in contents.split_inclusive('\n') {
+        if !replaced && line.contains("version") && line.contains(old) {
+            result.push_str(&line.replacen(old, new, 1));
+            replaced = true;
+        } else {
+            result.push_str(line);
+        }
+    }
+
+    replaced.then_some(result)
+}
+
+fn cargoversion(contents: &str) -> Option<(String, String)> {
+    let manifest = contents.parse::<toml::Table>().ok()?;
+    let package = manifest.get("package")?.as_table()?;
+    let name = package.get("name")?.as_str()?;
+    let version = package.get("version")?.as_str()?;
+    Some((name.to_string(), version.to_string()))
+}
+
+/// bumps the package's own entry in Cargo.lock so the lockfile isn't left stale
+fn replacelockversion(contents: &str, name: &str, old: &str, new: &str) -> Option<String> {
+    let entry = format!("name = \"{}\"\nversion = \"{}\"", name, old);
+    if !contents.contains(&entry) {
+        return None;
+    }
+    Some(contents.replacen(
+        &entry,
+        &format!("name = \"{}\"\nversion = \"{}\"", name, new),
+        1,
+    ))
+}
+
+fn releaseerror(message: String) -> MeowError {
+    MeowError::pipeline(Stage::Release, Cause::Unknown, message)
+}
+
+fn updatemanifest(
+    reporoot: &Path,
+    manifest: &str,
+    old: &str,
+    new: &str,
+    dryrun: &bool,
+) -> MeowResult<()> {
+    let path = reporoot.join(manifest);
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| releaseerror(format!("could not read {}: {}", manifest, e)))?;
+
+    let Some(updated) = replaceversion(&contents, old, new) else {
+        return Err(releaseerror(format!(
+            "could not find version {} in {}",
+            old, manifest
+        )));
+    };
+
+    info(&format!("    {}: {} -> {}", manifest, old, new));
+    if *dryrun {
+        return Ok(());
+    }
+
+    fs::write(&path, updated)
+        .map_err(|e| releaseerror(format!("could not write {}: {}", manifest, e)))
+}
+
+/// the next release, worked out from the history. it runs as a stage that
+/// bumps the manifests, in a pipeline that commits, tags and pushes them
+pub struct Release {
+    pub tagname: String,
+    /// the files the release rewrites, relative to the repository root
+    pub files: Vec<String>,
+    current: String,
+    next: String,
+    /// the package whose entry in Cargo.lock is bumped
+    lockpackage: Option<String>,
+    manifests: Vec<String>,
+    lasttag: Option<String>,
+    changelog: bool,
+    tagprefix: String,
+}
+
+pub fn planrelease(
+    reporoot: &Path,
+    config: &Config,
+    requested: Bump,
+    changelog: bool,
+    verbose: &u8,
+) -> MeowResult<Release> {
+    info("preparing release...");
+
+    debug("checking for staged changes", verbose);
+    if gitoutput(reporoot, &["diff", "--cached", "--quiet"]).is_err() {
+        return Err(releaseerror(String::from(
+            "there are staged changes. commit or unstage them before releasing",
+        )));
+    }
+
+    let cargomanifest = reporoot.join("Cargo.toml");
+    let cargo = if cargomanifest.is_file() {
+        let contents = fs::read_to_string(&cargomanifest)
+            .map_err(|e| releaseerror(format!("could not read Cargo.toml: {}", e)))?;
+        cargoversion(&contents)
+    } else {
+        None
+    };
+
+    let lasttag = lasttag(reporoot, "HEAD", &config.release.tagprefix);
+    let currentstr = match (&cargo, &lasttag) {
+        (Some((_, version)), _) => version.clone(),
+        (None, Some(tag)) => tag
+            .trim_start_matches(&config.release.tagprefix)
+            .to_string(),
+        (None, None) => {
+            return Err(releaseerror(String::from(
+                "could not determine the current version: no Cargo.toml and no tags",
+            )));
+        }
+    };
+    let Some(current) = Version::parse(&currentstr) else {
+        return Err(releaseerror(format!(
+            "current version {} is not a valid semver version",
+            currentstr
+        )));
+    };
+
+    let range = match &lasttag {
+        Some(tag) => {
+            info(&format!("    last tag: {}", tag));
+            format!("{}..HEAD", tag)
+        }
+        None => {
+            info("    no previous tag, using the full history");
+            String::from("HEAD")
+        }
+    };
+
+    let messages = commitmessages(reporoot, &range)?;
+    if messages.is_empty() {
+        return Err(releaseerror(String::from(
+            "nothing to release: no commits since the last tag",
+        )));
+    }
+    info(&format!(
+        "    {} commit(s) since last release",
+        messages.len()
+    ));
+
+    let bump = match requested {
+        Bump::Auto => {
+            let detected = detectbump(&messages);
+            debug(&format!("detected bump: {}", detected), verbose);
+            // breaking changes before 1.0 only bump the minor version
+            if detected == Bump::Major && current.major == 0 {
+                Bump::Minor
+            } else {
+                detected
+            }
+        }
+        bump => bump,
+    };
+
+    let next = current.bump(bump);
+    let tagname = format!("{}{}", config.release.tagprefix, next);
+    info(&format!("    {} release: {} -> {}", bump, current, next));
+
+    let mut files = Vec::new();
+    let mut lockpackage = None;
+    if let Some((name, _)) = cargo {
+        files.push(String::from("Cargo.toml"));
+
+        let lockfile = fs::read_to_string(reporoot.join("Cargo.lock")).ok();
+        let tracked = gitoutput(reporoot, &["ls-files", "--error-unmatch", "Cargo.lock"]).is_ok();
+        if lockfile.is_some_and(|contents| {
+            tracked && replacelockversion(&contents, &name, &currentstr, "").is_some()
+        }) {
+            files.push(String::from("Cargo.lock"));
+            lockpackage = Some(name);
+        }
+    }
+    files.extend(config.release.manifests.iter().cloned());
+    let changelog = changelog || config.release.changelog;
+    if changelog {
+        files.push(String::from(CHANGELOGFILE));
+    }
+    success("done");
+
+    Ok(Release {
+        tagname,
+        files,
+        current: currentstr,
+        next: next.to_string(),
+        lockpackage,
+        manifests: config.release.manifests.clone(),
+        lasttag,
+        changelog,
+        tagprefix: config.release.tagprefix.clone(),
+    })
+}
+
+impl Release {
+    /// adds the release to `pipeline`: the manifests are bumped once the
+    /// branch checks have passed, then committed, tagged and pushed
+    pub fn pipeline(self, pipeline: Pipeline, upstream: Option<String>) -> Pipeline {
+        let tag = TagOptions {
+            name: self.tagname.clone(),
+            message: Some(self.tagname.clone()),
+            sign: false,
+            force: false,
+        };
+        let pipeline = if self.files.is_empty() {
+            info("    no manifests to update, tagging HEAD");
+            pipeline
+        } else {
+            let message = format!("chore(release): {}", self.tagname);
+            let files = self.files.clone();
+            pipeline
+                .before(Anchor::Stage, Box::new(self))
+                .stagefiles(files)
+                .commit(message)
+        };
+
+        // a half-finished release is worse than none
+        pipeline.stoponerror(true).tag(tag).pushwith(PushOptions {
+            upstream,
+            force: 0,
+            ifincludes: false,
+        })
+    }
+
+    fn updatefiles(&self, reporoot: &Path, dryrun: &bool, verbose: &u8) -> MeowResult<()> {
+        if self.files.iter().any(|f| f == "Cargo.toml") {
+            updatemanifest(reporoot, "Cargo.toml", &self.current, &self.next, dryrun)?;
+        }
+        if let Some(name) = &self.lockpackage {
+            let lockpath = reporoot.join("Cargo.lock");
+            let contents = fs::read_to_string(&lockpath)
+                .map_err(|e| releaseerror(format!("could not read Cargo.lock: {}", e)))?;
+            let Some(updated) = replacelockversion(&contents, name, &self.current, &self.next)
+            else {
+                return Err(releaseerror(format!(
+                    "could not find {} {} in Cargo.lock",
+                    name, self.current
+                )));
+            };
+            info(&format!(
+                "    Cargo.lock: {} -> {}",
+                self.current, self.next
+            ));
+            if !*dryrun {
+                fs::write(&lockpath, updated)
+                    .map_err(|e| releaseerror(format!("could not write Cargo.lock: {}", e)))?;
+            }
+        }
+        for manifest in &self.manifests {
+            updatemanifest(reporoot, manifest, &self.current, &self.next, dryrun)?;
+        }
+
+        if self.changelog {
+            writechangelog(
+                reporoot,
+                self.lasttag.as_deref(),
+                "HEAD",
+                &self.tagprefix,
+                &self.tagname,
+                dryrun,
+                verbose,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl PipelineStage for Release {
+    fn stage(&self) -> Stage {
+        Stage::Release
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        info("\nupdating manifests...");
+        let result = self.updatefiles(ctx.repo, &ctx.dryrun, &ctx.verbose);
+        if result.is_ok() {
+            success("done");
+        }
+        result.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    fn messages(subjects: &[&str]) -> Vec<String> {
+        subjects.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parseversion() {
+        assert_eq!(Version::parse("1.2.3"), Some(version(1, 2, 3)));
+        assert_eq!(Version::parse(" v0.10.0\n"), Some(version(0, 10, 0)));
+    }
+
+    #[test]
+    fn parseprerelease() {
+        assert_eq!(Version::parse("v2.0.0-rc.1"), Some(version(2, 0, 0)));
+        assert_eq!(Version::parse("1.4.2+build.7"), Some(version(1, 4, 2)));
+    }
+
+    #[test]
+    fn parseinvalid() {
+        assert_eq!(Version::parse("1.2"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert_eq!(Version::parse("1.x.3"), None);
+        assert_eq!(Version::parse(""), None);
+    }
+
+    #[test]
+    fn bumpresetslower() {
+        assert_eq!(version(1, 2, 3).bump(Bump::Major), version(2, 0, 0));
+        assert_eq!(version(1, 2, 3).bump(Bump::Minor), version(1, 3, 0));
+        assert_eq!(version(1, 2, 3).bump(Bump::Patch), version(1, 2, 4));
+    }
+
+    #[test]
+    fn detectpatch() {
+        let messages = messages(&["fix: typo", "docs: readme", "not conventional"]);
+        assert_eq!(detectbump(&messages), Bump::Patch);
+        assert_eq!(detectbump(&[]), Bump::Patch);
+    }
+
+    #[test]
+    fn detectminor() {
+        let messages = messages(&["fix: typo", "feat(cli): new flag"]);
+        assert_eq!(detectbump(&messages), Bump::Minor);
+    }
+
+    #[test]
+    fn detectmajor() {
+        assert_eq!(
+            detectbump(&messages(&["feat: one", "fix!: drop old config"])),
+            Bump::Major
+        );
+        assert_eq!(
+            detectbump(&messages(&["refactor: x\n\nBREAKING CHANGE: renamed"])),
+            Bump::Major
+        );
+        assert_eq!(
+            detectbump(&messages(&["chore: y\n\nBREAKING-CHANGE: removed"])),
+            Bump::Major
+        );
+    }
+
+    #[test]
+    fn replacefirstversion() {
+        let manifest =
+            "[package]\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = { version = \"0.1.0\" }\n";
+        assert_eq!(
+            replaceversion(manifest, "0.1.0", "0.2.0").as_deref(),
+            Some(
+                "[package]\nversion = \"0.2.0\"\n\n[dependencies]\nfoo = { version = \"0.1.0\" }\n"
+            )
+        );
+    }
+
+    #[test]
+    fn replaceversionlineonly() {
+        let manifest = "name = \"pkg-0.1.0\"\nversion = \"0.1.0\"\n";
+        assert_eq!(
+            replaceversion(manifest, "0.1.0", "0.1.1").as_deref(),
+            Some("name = \"pkg-0.1.0\"\nversion = \"0.1.1\"\n")
+        );
+        assert_eq!(replaceversion(manifest, "9.9.9", "1.0.0"), None);
+    }
+
+    #[test]
+    fn replacelock() {
+        let lock = "[[package]]\nname = \"dep\"\nversion = \"0.1.0\"\n\n\
+                    [[package]]\nname = \"meow\"\nversion = \"0.1.0\"\n";
+        assert_eq!(
+            replacelockversion(lock, "meow", "0.1.0", "0.2.0").as_deref(),
+            Some(
+                "[[package]]\nname = \"dep\"\nversion = \"0.1.0\"\n\n\
+                 [[package]]\nname = \"meow\"\nversion = \"0.2.0\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn replacelockmissing() {
+        let lock = "[[package]]\nname = \"meow\"\nversion = \"0.1.0\"\n";
+        assert_eq!(replacelockversion(lock, "meow", "0.2.0", "0.3.0"), None);
+        assert_eq!(replacelockversion(lock, "other", "0.1.0", "0.2.0"), None);
+    }
+
+    #[test]
+    fn lasttagmatchesprefix() {
+        let root = std::env::temp_dir().join(format!("meow-lasttag-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(["-c", "user.name=meow", "-c", "user.email=meow@example.com"])
+                .args(["-c", "tag.gpgsign=false", "-c", "commit.gpgsign=false"])
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+        };
+        git(&["init", "-q"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "first"]);
+        git(&["tag", "-a", "v1.0.0", "-m", "v1.0.0"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "docs"]);
+        git(&["tag", "-a", "docs-1", "-m", "docs-1"]);
+
+        let release = lasttag(&root, "HEAD", "v");
+        let docs = lasttag(&root, "HEAD", "docs-");
+        let other = lasttag(&root, "HEAD", "release-");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(release.as_deref(), Some("v1.0.0"));
+        assert_eq!(docs.as_deref(), Some("docs-1"));
+        assert_eq!(other, None);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::{
+    artifacts::checkartifacts,
+    branches::{BranchesConfig, guardbranches},
+    error::{Cause, MeowError, MeowResult, Stage},
+    forcepush::{confirmforcepush, remoteref},
+    git::{commit, push, stage, stageall, tag},
+    hunks::stagehunks,
+    largefiles::{FilesConfig, LargeFilePolicy, checkstagedfiles},
+    loggers::*,
+    pipeline::{Files, Outcome, PipelineReport, PushOptions, TagOptions},
+    secrets::checksecrets,
+    submodules::{checkgitlinks, updatesubmodules},
+    tracking::printtracking,
+};
+use serde::Deserialize;
+use std::{path::Path, process::Command};
+
+/// the built-in stage a custom stage runs in front of
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    Stage,
+    Commit,
+    Push,
+}
+
+/// what the pipeline does when a custom stage fails
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    /// stop the pipeline and exit with the stage's error
+    #[default]
+    Abort,
+    /// report the failure and carry on
+    Warn,
+    /// carry on without reporting (shown with -v)
+    Ignore,
+}
+
+pub struct StageContext<'a> {
+    pub repo: &'a Path,
+    pub dryrun: bool,
+    pub verbose: u8,
+    /// the steps that have already run
+    pub report: &'a PipelineReport,
+}
+
+/// one step of the pipeline. the built-in steps implement this, as do
+/// the custom commands from config
+pub trait PipelineStage {
+    fn stage(&self) -> Stage;
+
+    fn name(&self) -> String {
+        self.stage().name().to_string()
+    }
+
+    /// `None` follows the pipeline's stop-on-error setting
+    fn onfailure(&self) -> Option<OnFailure> {
+        None
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome;
+}
+
+impl<T> From<MeowResult<T>> for Outcome {
+    fn from(result: MeowResult<T>) -> Outcome {
+        match result {
+            Ok(_) => Outcome::Succeeded,
+            Err(e) => Outcome::Failed(e),
+        }
+    }
+}
+
+pub struct SubmodulesStage<'a> {
+    pub message: &'a str,
+    pub push: bool,
+    pub allowsecrets: bool,
+    pub assumeyes: bool,
+}
+
+impl PipelineStage for SubmodulesStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Submodules
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        updatesubmodules(
+            ctx.repo,
+            self.message,
+            self.push,
+            &self.allowsecrets,
+            &self.assumeyes,
+            &ctx.dryrun,
+            &ctx.verbose,
+        )
+        .into()
+    }
+}
+
+pub struct AddStage<'a> {
+    pub files: &'a Files,
+    /// look for build artifacts before `git add .`. off when
+    /// `Pipeline::confirm` already did
+    pub checkartifacts: bool,
+}
+
+impl PipelineStage for AddStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Add
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        info("staging changes...");
+        debug(
+            "checking if files were specified to be staged",
+            &ctx.verbose,
+        );
+        let result = match self.files {
+            Files::Paths(paths) => stage(ctx.repo, paths, &ctx.dryrun, &ctx.verbose),
+            Files::Hunks(paths) => stagehunks(ctx.repo, paths, &ctx.dryrun, &ctx.verbose),
+            Files::All if self.checkartifacts => {
+                checkartifacts(ctx.repo, &ctx.dryrun, &ctx.verbose)
+                    .and_then(|_| stageall(ctx.repo, &ctx.dryrun, &ctx.verbose))
+            }
+            Files::All => stageall(ctx.repo, &ctx.dryrun, &ctx.verbose),
+        };
+        if result.is_ok() {
+            success("done");
+        }
+        result.into()
+    }
+}
+
+pub struct FilesStage<'a> {
+    pub config: &'a FilesConfig,
+    /// nobody is there to answer the large-file prompt, so it refuses instead
+    pub unattended: bool,
+}
+
+impl PipelineStage for FilesStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Files
+    }
+
+    // a blocked file stops the pipeline even without --exit
+    fn onfailure(&self) -> Option<OnFailure> {
+        Some(OnFailure::Abort)
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        if ctx.dryrun {
+            debug(
+                "dry run was specified, nothing was staged to check",
+                &ctx.verbose,
+            );
+            return Outcome::Skipped(String::from("dry run"));
+        }
+        if self.unattended && self.config.onlarge == LargeFilePolicy::Prompt {
+            let config = FilesConfig {
+                onlarge: LargeFilePolicy::Block,
+                ..self.config.clone()
+            };
+            return checkstagedfiles(ctx.repo, &config, &ctx.verbose).into();
+        }
+        checkstagedfiles(ctx.repo, self.config, &ctx.verbose).into()
+    }
+}
+
+pub struct BranchStage<'a> {
+    pub config: &'a BranchesConfig,
+    pub commit: bool,
+    pub push: Option<&'a PushOptions>,
+    pub forceprotected: bool,
+}
+
+impl PipelineStage for BranchStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Branch
+    }
+
+    // a declined confirmation stops the pipeline even without --exit
+    fn onfailure(&self) -> Option<OnFailure> {
+        Some(OnFailure::Abort)
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        guardbranches(
+            ctx.repo,
+            self.config,
+            self.commit,
+            self.push
+                .map(|options| (options.upstream.as_deref(), options.force)),
+            &self.forceprotected,
+            &ctx.dryrun,
+            &ctx.verbose,
+        )
+        .into()
+    }
+}
+
+pub struct SecretsStage {
+    /// report findings without blocking the commit
+    pub allow: bool,
+}
+
+impl PipelineStage for SecretsStage {
+    fn stage(&self) -> Stage {
+        Stage::Secrets
+    }
+
+    // blocks the commit even without --exit
+    fn onfailure(&self) -> Option<OnFailure> {
+        Some(OnFailure::Abort)
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        checksecrets(ctx.repo, &self.allow, &ctx.verbose).into()
+    }
+}
+
+pub struct CommitStage<'a> {
+    pub message: &'a str,
+}
+
+impl PipelineStage for CommitStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Commit
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        info("\ncommitting...");
+        let result = commit(ctx.repo, self.message, &ctx.dryrun, &ctx.verbose);
+        if result.is_ok() {
+            success("done");
+        }
+        result.into()
+    }
+}
+
+pub struct TagStage<'a> {
+    pub options: &'a TagOptions,
+    pub message: Option<&'a str>,
+}
+
+impl PipelineStage for TagStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Tag
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        let options = self.options;
+        debug(&format!("tag {} was specified", options.name), &ctx.verbose);
+        info("\ntagging...");
+        let tagmessage = match (&options.message, self.message) {
+            (Some(m), _) => m.as_str(),
+            (None, Some(m)) if !m.is_empty() => m,
+            _ => options.name.as_str(),
+        };
+
+        if matches!(ctx.report.outcome(Stage::Commit), Some(Outcome::Failed(_))) {
+            error("    not tagging: the commit stage failed");
+            return Outcome::Skipped(String::from("commit failed"));
+        }
+
+        let result = tag(
+            ctx.repo,
+            &options.name,
+            tagmessage,
+            &options.sign,
+            &options.force,
+            &ctx.dryrun,
+            &ctx.verbose,
+        );
+        if result.is_ok() {
+            success("done");
+        }
+        result.into()
+    }
+}
+
+pub struct PushStage<'a> {
+    pub options: &'a PushOptions,
+    pub tag: Option<&'a TagOptions>,
+    /// the force push was confirmed before the run, by `Pipeline::confirm`
+    pub unattended: bool,
+}
+
+impl PipelineStage for PushStage<'_> {
+    fn stage(&self) -> Stage {
+        Stage::Push
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        info("\npushing...");
+        // only push the tag if this run created it
+        let pushtag = self
+            .tag
+            .filter(|_| matches!(ctx.report.outcome(Stage::Tag), Some(Outcome::Succeeded)))
+            .map(|t| t.name.as_str());
+        let forcetag = self.tag.is_some_and(|t| t.force);
+        let upstream = self.options.upstream.as_deref();
+        let newbranch = remoteref(ctx.repo, upstream).is_none();
+
+        let result = match checkgitlinks(ctx.repo, &ctx.verbose) {
+            Err(e) if !ctx.dryrun => Err(e),
+            _ if self.unattended => Ok(()),
+            _ => confirmforcepush(
+                ctx.repo,
+                upstream,
+                &self.options.force,
+                &ctx.dryrun,
+                &ctx.verbose,
+            ),
+        }
+        .and_then(|_| {
+            push(
+                ctx.repo,
+                upstream,
+                pushtag,
+                &ctx.dryrun,
+                &self.options.force,
+                &self.options.ifincludes,
+                &forcetag,
+                &ctx.verbose,
+            )
+        });
+
+        if result.is_ok() && !ctx.dryrun {
+            printtracking(ctx.repo, newbranch, &ctx.verbose);
+        }
+        result.into()
+    }
+}
+
+/// a user-defined stage from config, e.g. `cargo clippy` before committing
+#[derive(Deserialize, Debug, Clone)]
+pub struct CustomStage {
+    pub name: String,
+    /// shell command, run from the repository root
+    pub run: String,
+    pub before: Anchor,
+    #[serde(rename = "on-failure", default)]
+    pub onfailure: OnFailure,
+}
+
+pub(crate) fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+impl PipelineStage for CustomStage {
+    fn stage(&self) -> Stage {
+        Stage::Custom
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn onfailure(&self) -> Option<OnFailure> {
+        Some(self.onfailure)
+    }
+
+    fn run(&self, ctx: &StageContext) -> Outcome {
+        info(&format!("\nrunning {}...", self.name));
+        printcommand(&[&self.run]);
+
+        if ctx.dryrun {
+            debug(
+                "dry run was specified, not running custom stage",
+                &ctx.verbose,
+            );
+            return Outcome::Skipped(String::from("dry run"));
+        }
+
+        let output = match shell(&self.run).current_dir(ctx.repo).output() {
+            Ok(o) => o,
+            Err(e) => {
+                return Outcome::Failed(MeowError::Spawn {
+                    stage: Stage::Custom,
+                    command: self.run.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if output.status.success() {
+            for line in stdout.lines().chain(stderr.lines()) {
+                debug(&format!("{}: {}", self.name, line), &ctx.verbose);
+            }
+            success("done");
+            return Outcome::Succeeded;
+        }
+
+        if self.onfailure != OnFailure::Ignore {
+            for line in stdout.lines().chain(stderr.lines()) {
+                info(&format!("      {}", line));
+            }
+        }
+        Outcome::Failed(MeowError::Command {
+            stage: Stage::Custom,
+            command: self.run.clone(),
+            exitcode: output.status.code(),
+            stdout,
+            stderr,
+            cause: Cause::StageFailed(self.name.clone()),
+        })
+    }
+}
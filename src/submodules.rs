@@ -0,0 +1,219 @@
+use crate::{
+    artifacts::checkartifacts,
+    error::{Cause, MeowError, MeowResult, Stage},
+    git::{commit, gitoutput, push, stage, stageall},
+    loggers::*,
+    secrets::checksecrets,
+};
+use console::style;
+use dialoguer::Confirm;
+use std::path::{Path, PathBuf};
+
+pub struct Submodule {
+    pub path: String,
+    pub dirty: bool,
+    pub unpushed: bool,
+}
+
+impl Submodule {
+    fn fullpath(&self, reporoot: &Path) -> PathBuf {
+        reporoot.join(&self.path)
+    }
+}
+
+pub fn submodulepaths(reporoot: &Path) -> Vec<String> {
+    if !reporoot.join(".gitmodules").is_file() {
+        return Vec::new();
+    }
+
+    gitoutput(
+        reporoot,
+        &[
+            "config",
+            "--file",
+            ".gitmodules",
+            "--get-regexp",
+            r"^submodule\..*\.path$",
+        ],
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+        line.split_once(' ')
+            .map(|(_, path)| path.trim().to_string())
+    })
+    .collect()
+}
+
+fn isinitialized(path: &Path) -> bool {
+    path.join(".git").exists()
+}
+
+// submodules are checked out at a commit, not a branch, until someone switches
+fn isdetached(path: &Path) -> bool {
+    gitoutput(path, &["symbolic-ref", "--quiet", "HEAD"]).is_err()
+}
+
+/// whether `rev` is missing from every remote-tracking ref of the repo at `path`
+fn isunpublished(path: &Path, rev: &str) -> bool {
+    gitoutput(path, &["rev-list", "-n1", rev, "--not", "--remotes"])
+        .is_ok_and(|o| !o.trim().is_empty())
+}
+
+/// submodules with uncommitted changes or commits that haven't been pushed
+pub fn changedsubmodules(reporoot: &Path) -> Vec<Submodule> {
+    submodulepaths(reporoot)
+        .into_iter()
+        .filter_map(|path| {
+            let fullpath = reporoot.join(&path);
+            if !isinitialized(&fullpath) {
+                return None;
+            }
+
+            let dirty = gitoutput(&fullpath, &["status", "--porcelain"])
+                .is_ok_and(|o| !o.trim().is_empty());
+            let unpushed = isunpublished(&fullpath, "HEAD");
+
+            (dirty || unpushed).then_some(Submodule {
+                path,
+                dirty,
+                unpushed,
+            })
+        })
+        .collect()
+}
+
+/// submodule commits referenced by the parent's HEAD that no remote has
+pub fn unpublishedgitlinks(reporoot: &Path) -> Vec<(String, String)> {
+    submodulepaths(reporoot)
+        .into_iter()
+        .filter_map(|path| {
+            let fullpath = reporoot.join(&path);
+            if !isinitialized(&fullpath) {
+                return None;
+            }
+
+            let tree = gitoutput(reporoot, &["ls-tree", "HEAD", &path]).ok()?;
+            // <mode> commit <sha>\t<path>
+            let sha = tree.split_whitespace().nth(2)?.to_string();
+            isunpublished(&fullpath, &sha).then_some((path, sha))
+        })
+        .collect()
+}
+
+fn confirm(prompt: &str, assumeyes: &bool) -> bool {
+    if *assumeyes {
+        return true;
+    }
+
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(true)
+        .interact()
+        .unwrap_or(false)
+}
+
+/// commits and pushes inside each changed submodule, then stages the updated gitlinks.
+/// the submodule's commit goes through the same artifact and secret checks as the
+/// parent's. returns the submodules that were updated
+pub fn updatesubmodules(
+    reporoot: &Path,
+    message: &str,
+    runpush: bool,
+    allowsecrets: &bool,
+    assumeyes: &bool,
+    dryrun: &bool,
+    verbose: &u8,
+) -> MeowResult<Vec<String>> {
+    debug("checking submodules for changes", verbose);
+    let changed = changedsubmodules(reporoot);
+    if changed.is_empty() {
+        debug("no submodules with changes", verbose);
+        return Ok(Vec::new());
+    }
+
+    info("submodules with changes:");
+    for submodule in &changed {
+        let mut state = Vec::new();
+        if submodule.dirty {
+            state.push("uncommitted changes");
+        }
+        if submodule.unpushed {
+            state.push("unpushed commits");
+        }
+        info(&format!(
+            "    {} ({})",
+            style(&submodule.path).bold(),
+            state.join(", ")
+        ));
+    }
+
+    let mut updated = Vec::new();
+    for submodule in &changed {
+        let prompt = if runpush {
+            format!("commit and push inside {} first?", submodule.path)
+        } else {
+            format!("commit inside {} first?", submodule.path)
+        };
+        if !confirm(&prompt, assumeyes) {
+            info(&format!("    skipping {}", submodule.path));
+            continue;
+        }
+
+        let fullpath = submodule.fullpath(reporoot);
+        // checked before committing, so a refusal leaves the submodule as it was
+        if runpush && isdetached(&fullpath) {
+            return Err(MeowError::pipeline(
+                Stage::Submodules,
+                Cause::DetachedHead,
+                format!(
+                    "not pushing submodule {}: it isn't on a branch",
+                    submodule.path
+                ),
+            ));
+        }
+
+        info(&format!("\nupdating submodule {}...", submodule.path));
+        if submodule.dirty {
+            checkartifacts(&fullpath, dryrun, verbose)?;
+            stageall(&fullpath, dryrun, verbose)?;
+            checksecrets(&fullpath, allowsecrets, verbose)?;
+            commit(&fullpath, message, dryrun, verbose)?;
+        }
+        if runpush {
+            push(&fullpath, None, None, dryrun, &0, &false, &false, verbose)?;
+        }
+        success("done");
+        updated.push(submodule.path.clone());
+    }
+
+    if !updated.is_empty() {
+        info("\nstaging updated submodules...");
+        stage(reporoot, &updated, dryrun, verbose)?;
+        success("done\n");
+    }
+
+    Ok(updated)
+}
+
+/// refuses to push the parent while it points at submodule commits no remote has
+pub fn checkgitlinks(reporoot: &Path, verbose: &u8) -> MeowResult<()> {
+    debug("checking submodule commits are published", verbose);
+    let unpublished = unpublishedgitlinks(reporoot);
+    if unpublished.is_empty() {
+        return Ok(());
+    }
+
+    for (path, sha) in &unpublished {
+        error(&format!(
+            "    submodule {} points at {} which hasn't been pushed",
+            style(path).bold(),
+            &sha[..sha.len().min(7)]
+        ));
+    }
+    Err(MeowError::pipeline(
+        Stage::Push,
+        Cause::UnpublishedSubmodule,
+        "not pushing: the parent references unpushed submodule commits",
+    ))
+}
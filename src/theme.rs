@@ -0,0 +1,170 @@
+use crate::loggers::{Level, setascii, settheme};
+use clap::ValueEnum;
+use console::{Style, Term};
+use serde::Deserialize;
+use std::env;
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// color when writing to a terminal, unless NO_COLOR or CLICOLOR say otherwise
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub color: ColorChoice,
+    /// plain ascii output: no emoji or arrows
+    pub ascii: bool,
+}
+
+/// the style of each message level, in console's dotted notation
+/// (`"yellow.bold"`, `"black.on_white"`, ...)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub important: String,
+    pub info: String,
+    pub success: String,
+    pub error: String,
+    pub hint: String,
+    pub debug: String,
+    pub trace: String,
+    pub command: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            important: String::from("cyan"),
+            info: String::from("magenta"),
+            success: String::from("green"),
+            error: String::from("red"),
+            hint: String::from("yellow"),
+            debug: String::from("blue"),
+            trace: String::from("blue.dim"),
+            command: String::from("cyan"),
+        }
+    }
+}
+
+impl Theme {
+    pub fn style(&self, level: Level) -> Style {
+        let dotted = match level {
+            Level::Important => &self.important,
+            Level::Info => &self.info,
+            Level::Success => &self.success,
+            Level::Error => &self.error,
+            Level::Hint => &self.hint,
+            Level::Debug => &self.debug,
+            Level::Trace => &self.trace,
+            Level::Command => &self.command,
+            Level::Plain => return Style::new(),
+        };
+        Style::from_dotted_str(dotted)
+    }
+}
+
+// NO_COLOR turns color off, CLICOLOR_FORCE turns it on even when piped and
+// CLICOLOR=0 turns it off. None leaves it to terminal detection
+fn envcolors(var: impl Fn(&str) -> Option<String>) -> Option<bool> {
+    let set = |name: &str| var(name).filter(|v| !v.is_empty());
+
+    if set("NO_COLOR").is_some() {
+        Some(false)
+    } else if set("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+        Some(true)
+    } else if set("CLICOLOR").is_some_and(|v| v == "0") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// applies the color choice, ascii mode and theme to all output. a `--color`
+/// flag beats the config, which beats the environment
+pub fn configureoutput(
+    flag: Option<ColorChoice>,
+    config: &OutputConfig,
+    theme: &Theme,
+    ascii: bool,
+) {
+    let choice = match flag {
+        Some(choice) => choice,
+        None => config.color,
+    };
+    let (stdout, stderr) = match choice {
+        ColorChoice::Always => (true, true),
+        ColorChoice::Never => (false, false),
+        ColorChoice::Auto => match envcolors(|name| env::var(name).ok()) {
+            Some(enabled) => (enabled, enabled),
+            None => (
+                Term::stdout().features().colors_supported(),
+                Term::stderr().features().colors_supported(),
+            ),
+        },
+    };
+    console::set_colors_enabled(stdout);
+    console::set_colors_enabled_stderr(stderr);
+
+    setascii(ascii || config.ascii);
+    settheme(theme.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(set: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let set: Vec<(String, String)> = set
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| {
+            set.iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn colorsfromenv() {
+        assert_eq!(envcolors(vars(&[])), None);
+        assert_eq!(envcolors(vars(&[("NO_COLOR", "1")])), Some(false));
+        assert_eq!(envcolors(vars(&[("NO_COLOR", "")])), None);
+        assert_eq!(envcolors(vars(&[("CLICOLOR_FORCE", "1")])), Some(true));
+        assert_eq!(envcolors(vars(&[("CLICOLOR_FORCE", "0")])), None);
+        assert_eq!(envcolors(vars(&[("CLICOLOR", "0")])), Some(false));
+        assert_eq!(envcolors(vars(&[("CLICOLOR", "1")])), None);
+        // NO_COLOR wins over forcing it on
+        assert_eq!(
+            envcolors(vars(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn themestyles() {
+        let theme = Theme {
+            error: String::from("black.on_white"),
+            ..Theme::default()
+        };
+        assert_eq!(
+            theme.style(Level::Error),
+            Style::from_dotted_str("black.on_white")
+        );
+        assert_eq!(theme.style(Level::Success), Style::new().green());
+        assert_eq!(theme.style(Level::Plain), Style::new());
+    }
+
+    #[test]
+    fn partialtheme() {
+        let theme: Theme = toml::from_str("hint = \"magenta.bold\"").unwrap();
+        assert_eq!(theme.hint, "magenta.bold");
+        assert_eq!(theme.error, Theme::default().error);
+    }
+}
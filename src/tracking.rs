@@ -0,0 +1,147 @@
+use crate::{
+    branches::currentbranch, changelog::remoteweburl, error::Cause, git::gitoutput, loggers::*,
+};
+use console::style;
+use std::path::Path;
+
+pub struct TrackingStatus {
+    pub branch: String,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    /// short hash and subject of the commit the remote branch points at
+    pub lastpushed: Option<(String, String)>,
+}
+
+pub fn trackingstatus(repo: &Path) -> Option<TrackingStatus> {
+    let branch = currentbranch(repo)?;
+    let upstream = gitoutput(repo, &["rev-parse", "--abbrev-ref", "@{upstream}"])
+        .ok()
+        .map(|u| u.trim().to_string());
+
+    // <ahead>\t<behind>
+    let (ahead, behind) = gitoutput(
+        repo,
+        &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+    )
+    .ok()
+    .and_then(|counts| {
+        let mut parts = counts.split_whitespace().map(|n| n.parse().unwrap_or(0));
+        Some((parts.next()?, parts.next()?))
+    })
+    .unwrap_or((0, 0));
+
+    let lastpushed = ["@{push}", "@{upstream}"].iter().find_map(|rev| {
+        let line = gitoutput(repo, &["log", "-1", "--format=%h%x09%s", rev]).ok()?;
+        let (hash, subject) = line.trim().split_once('\t')?;
+        Some((hash.to_string(), subject.to_string()))
+    });
+
+    Some(TrackingStatus {
+        branch,
+        upstream,
+        ahead,
+        behind,
+        lastpushed,
+    })
+}
+
+/// the page for opening a pull/merge request from `branch`, by hosting service
+pub fn compareurl(baseurl: &str, branch: &str) -> String {
+    if baseurl.contains("gitlab") {
+        format!(
+            "{}/-/merge_requests/new?merge_request[source_branch]={}",
+            baseurl, branch
+        )
+    } else if baseurl.contains("bitbucket") {
+        format!("{}/pull-requests/new?source={}", baseurl, branch)
+    } else {
+        format!("{}/compare/{}?expand=1", baseurl, branch)
+    }
+}
+
+fn syncstate(ahead: usize, behind: usize) -> String {
+    match (ahead, behind) {
+        (0, 0) => String::from("up to date"),
+        (ahead, 0) => format!("{} ahead", ahead),
+        (0, behind) => format!("{} behind", behind),
+        (ahead, behind) => format!("{} ahead, {} behind", ahead, behind),
+    }
+}
+
+/// prints the branch's relationship to its upstream after a push. `newbranch`
+/// means the remote didn't have the branch before
+pub fn printtracking(repo: &Path, newbranch: bool, verbose: &u8) {
+    debug("reading tracking status", verbose);
+    let Some(status) = trackingstatus(repo) else {
+        debug("not on a branch, no tracking status", verbose);
+        return;
+    };
+
+    let Some(upstream) = &status.upstream else {
+        let hint = Cause::NoUpstream.hint().unwrap_or_default();
+        info(&format!(
+            "    {} has no upstream. {}",
+            style(&status.branch).bold(),
+            hint.replace("<branch>", &status.branch)
+        ));
+        return;
+    };
+
+    info(&format!(
+        "    {} -> {} ({})",
+        style(&status.branch).bold(),
+        upstream,
+        syncstate(status.ahead, status.behind)
+    ));
+
+    if let Some((hash, subject)) = &status.lastpushed {
+        info(&format!(
+            "    last pushed: {} {}",
+            style(hash).yellow(),
+            subject
+        ));
+    }
+
+    if newbranch && let Some(baseurl) = remoteweburl(repo) {
+        let request = if baseurl.contains("gitlab") {
+            "merge"
+        } else {
+            "pull"
+        };
+        info(&format!(
+            "    open a {} request: {}",
+            request,
+            style(compareurl(&baseurl, &status.branch)).underlined()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compareurls() {
+        assert_eq!(
+            compareurl("https://github.com/me/meow", "fix/typo"),
+            "https://github.com/me/meow/compare/fix/typo?expand=1"
+        );
+        assert_eq!(
+            compareurl("https://gitlab.com/me/meow", "wip"),
+            "https://gitlab.com/me/meow/-/merge_requests/new?merge_request[source_branch]=wip"
+        );
+        assert_eq!(
+            compareurl("https://bitbucket.org/me/meow", "wip"),
+            "https://bitbucket.org/me/meow/pull-requests/new?source=wip"
+        );
+    }
+
+    #[test]
+    fn syncstates() {
+        assert_eq!(syncstate(0, 0), "up to date");
+        assert_eq!(syncstate(2, 0), "2 ahead");
+        assert_eq!(syncstate(0, 3), "3 behind");
+        assert_eq!(syncstate(1, 4), "1 ahead, 4 behind");
+    }
+}
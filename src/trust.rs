@@ -0,0 +1,104 @@
+use crate::{
+    config::{Config, REPOCONFIGFILE},
+    logfile::statedir,
+    loggers::*,
+    stages::CustomStage,
+};
+use dialoguer::Confirm;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+// repository root -> the stage commands the user agreed to run there
+fn trustfile() -> Option<PathBuf> {
+    statedir().map(|dir| dir.join("trusted.toml"))
+}
+
+fn readtrusted() -> Table {
+    trustfile()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| contents.parse::<Table>().ok())
+        .unwrap_or_default()
+}
+
+fn commands(stages: &[CustomStage]) -> Vec<Value> {
+    stages
+        .iter()
+        .map(|stage| Value::String(stage.run.clone()))
+        .collect()
+}
+
+fn savetrusted(repo: &str, stages: &[CustomStage], verbose: &u8) {
+    let Some(path) = trustfile() else {
+        debug("no state directory, not remembering the trust", verbose);
+        return;
+    };
+    let mut trusted = readtrusted();
+    trusted.insert(repo.to_string(), Value::Array(commands(stages)));
+
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, trusted.to_string()));
+    if let Err(e) = saved {
+        debug(
+            &format!("could not write {}: {}", path.display(), e),
+            verbose,
+        );
+    }
+}
+
+/// the custom stages that may run in `reporoot`. stages from the global config
+/// always may. stages from the repository's .meow.toml could have been written
+/// by anyone with push access, so they only run once the user has agreed to
+/// their exact commands. the answer is stored outside the repository, and any
+/// change to the commands asks again
+pub fn trustedstages<'a>(
+    reporoot: &Path,
+    config: &'a Config,
+    dryrun: &bool,
+    verbose: &u8,
+) -> &'a [CustomStage] {
+    if !config.repostages || config.stages.is_empty() {
+        return &config.stages;
+    }
+
+    let repo = reporoot
+        .canonicalize()
+        .unwrap_or_else(|_| reporoot.to_path_buf())
+        .to_string_lossy()
+        .into_owned();
+    if readtrusted().get(&repo) == Some(&Value::Array(commands(&config.stages))) {
+        debug("the repository's custom stages are trusted", verbose);
+        return &config.stages;
+    }
+
+    error(&format!(
+        "    this repository's {} runs these commands:",
+        REPOCONFIGFILE
+    ));
+    for stage in &config.stages {
+        detail(&format!("      {}: {}", stage.name, stage.run));
+    }
+
+    // a dry run only prints the commands, so there's nothing to trust yet
+    if *dryrun {
+        info("    would ask whether to trust them");
+        return &config.stages;
+    }
+
+    let trusted = Confirm::new()
+        .with_prompt("trust them and run them from now on?")
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if trusted {
+        savetrusted(&repo, &config.stages, verbose);
+        &config.stages
+    } else {
+        info("    continuing without the repository's custom stages");
+        &[]
+    }
+}